@@ -0,0 +1,490 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_openai::Client;
+use async_openai::config::OpenAIConfig;
+use chess::{Board, ChessMove};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use tokio::time::timeout;
+use tokio_util::sync::CancellationToken;
+
+use crate::backend::chat::ChatBackend;
+use crate::backend::mock::MockBackend;
+use crate::backend::replay::ReplayBackend;
+use crate::backend::responses::ResponsesBackend;
+use crate::backend::score::Score;
+use crate::fen2md::fen2md;
+use crate::outputln;
+use crate::state::game::Game;
+use crate::state::options::{BackendKind, Options};
+
+pub mod chat;
+pub mod mock;
+pub mod record;
+pub mod replay;
+pub mod responses;
+pub mod score;
+pub mod tools;
+mod wdl;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubmitEval {
+    ponder: Vec<String>,
+    eval: f32,
+    mate: Option<f32>,
+    depth: Option<f32>,
+    win: Option<f32>,
+    draw: Option<f32>,
+    loss: Option<f32>,
+    reasoning: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubmitLines {
+    lines: Vec<SubmitEval>,
+    reasoning: Option<String>,
+}
+
+/// A single validated line returned by the ai
+#[derive(Debug, Clone)]
+pub struct Line {
+    pub bestmove: ChessMove,
+    /// The line, cut off before the first illegal move
+    pub pv: String,
+    /// The reply the ai expects to the best move
+    pub ponder: Option<ChessMove>,
+    pub score: Score,
+    pub depth: u32,
+    /// The win/draw/loss permille the ai submitted, from the side to move's point of view
+    pub wdl: Option<[u32; 3]>,
+}
+
+/// The most recent lines reported by the ai, kept around in case the search has to end early
+pub type Latest = Arc<Mutex<Option<Vec<Line>>>>;
+
+/// The last lines the ai gave for each position of the game, keyed by `cache_key`
+pub type Cache = Arc<Mutex<HashMap<String, Vec<Line>>>>;
+
+/// The position part of the fen, the move clocks don't matter to the cache
+pub fn cache_key(board: &Board) -> String {
+    board.to_string().split_whitespace().take(4).collect::<Vec<_>>().join(" ")
+}
+
+/// Everything a backend needs to know to evaluate a position
+#[derive(Debug, Clone)]
+pub struct Query {
+    pub options: Options,
+    pub game: Game,
+    pub legal_moves: Vec<ChessMove>,
+    /// Summary of an earlier analysis of this position the ai should refine
+    pub previous: Option<String>,
+    pub latest: Latest,
+    /// How many times this query was already tried
+    pub retry: u8,
+    /// Cancelled by `stop`, dropping the request that is in flight
+    pub cancellation: CancellationToken,
+    /// One of several parallel samples, which are only reported once they have voted
+    pub sampled: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    System,
+    User,
+}
+
+/// A backend-agnostic prompt message, each backend converts them into its own request format
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub role: Role,
+    pub text: String,
+}
+
+impl Message {
+    pub fn system(text: impl Into<String>) -> Self {
+        Self { role: Role::System, text: text.into() }
+    }
+
+    pub fn user(text: impl Into<String>) -> Self {
+        Self { role: Role::User, text: text.into() }
+    }
+}
+
+pub enum Backend {
+    Responses(ResponsesBackend),
+    Chat(ChatBackend),
+    Mock(MockBackend),
+    Replay(ReplayBackend),
+}
+
+impl From<BackendKind> for Backend {
+    fn from(kind: BackendKind) -> Self {
+        match kind {
+            BackendKind::Responses => Backend::Responses(ResponsesBackend),
+            BackendKind::ChatCompletions => Backend::Chat(ChatBackend),
+            BackendKind::Mock => Backend::Mock(MockBackend),
+            BackendKind::Replay => Backend::Replay(ReplayBackend),
+        }
+    }
+}
+
+impl IBackend for Backend {
+    async fn evaluate(&self, query: Query) -> Option<Vec<Line>> {
+        match self {
+            Backend::Responses(backend) => backend.evaluate(query).await,
+            Backend::Chat(backend) => backend.evaluate(query).await,
+            Backend::Mock(backend) => backend.evaluate(query).await,
+            Backend::Replay(backend) => backend.evaluate(query).await,
+        }
+    }
+}
+
+pub trait IBackend {
+    /// Ask the ai about the position. The returned lines are validated, reported and start with distinct legal moves
+    async fn evaluate(&self, query: Query) -> Option<Vec<Line>>;
+}
+
+/// Play the line on the board, cutting it off at the first move that isn't legal
+fn valid_pv(mut game: Game, ponder: &[String]) -> Vec<ChessMove> {
+    let mut pv = vec![];
+    for (ply, mov) in ponder.iter().enumerate() {
+        let Some(mov) = ChessMove::from_str(mov).ok().filter(|x| game.is_legal(*x)) else {
+            outputln!(
+                "info string error: ai's line has the illegal move {mov} at ply {}, cutting the line off before it",
+                ply + 1
+            );
+            break;
+        };
+        pv.push(mov);
+        game.push(mov);
+    }
+    pv
+}
+
+pub fn line_from_eval(eval: SubmitEval, legal_moves: &[ChessMove], game: &Game) -> Option<Line> {
+    let depth = eval.depth.unwrap_or(1.0) as u32;
+    let score = Score::from_eval(eval.eval, eval.mate, game.board().side_to_move());
+
+    if eval.ponder.is_empty() {
+        outputln!("info string error: ai returned no ponder");
+        return None;
+    }
+
+    let bm = eval.ponder.first().unwrap();
+    if !legal_moves.iter().any(|x| x.to_string() == *bm) {
+        outputln!("info string error: ai returned an illegal move");
+        return None;
+    }
+
+    let bestmove = ChessMove::from_str(bm).unwrap();
+    let pv = valid_pv(game.clone(), &eval.ponder);
+    let ponder = pv.get(1).copied();
+    let pv = pv.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(" ");
+
+    let wdl = match (eval.win, eval.draw, eval.loss) {
+        (Some(win), Some(draw), Some(loss)) => match wdl::validate(win, draw, loss) {
+            Ok(wdl) => Some(wdl),
+            Err(err) => {
+                outputln!("info string error: ai returned {err}, deriving it from the eval instead");
+                None
+            }
+        },
+        _ => None,
+    };
+
+    Some(Line { bestmove, pv, ponder, score, depth, wdl })
+}
+
+/// How many lines a weakened engine asks for at least, so it has some alternatives to the best move
+const LIMIT_STRENGTH_LINES: u8 = 3;
+
+pub fn multipv(options: &Options, legal_moves: &[ChessMove]) -> usize {
+    let multipv = match options.uci_limit_strength {
+        true => options.multipv.max(LIMIT_STRENGTH_LINES),
+        false => options.multipv,
+    };
+    (multipv as usize).clamp(1, legal_moves.len())
+}
+
+pub fn eval_schema(options: &Options, legal_moves: &[ChessMove]) -> Value {
+    let multipv = multipv(options, legal_moves);
+
+    let mut submit_eval_schema = json!({
+        "type": "object",
+        "properties": {
+            "ponder": {
+                "type": "array",
+                // only the first move is one of the legal moves, the rest are played on the boards after it
+                "prefixItems": [{
+                    "type": "string",
+                    "enum": legal_moves.iter().map(|x| x.to_string()).collect::<Vec<String>>()
+                }],
+                "items": {
+                    "type": "string",
+                    "pattern": "^[a-h][1-8][a-h][1-8][qrbn]?$"
+                },
+                "minItems": 1,
+                "description": "This is the list of moves you think is the best line. For example, if you think that the best line from a starting position is 1. d4 d5, this field should be [\"d2d4\",\"d7d5\"], provided \"d2d4\" is one of the legal moves."
+            },
+            "eval": {
+                "type": "number",
+                "description": "The evaluation score in pawns from white's point of view. For example, if this is 0.9, white has a 0.9 pawn in advantage and if it is -1, black has a 1 pawn advantage, and if this is 0, the game is equal."
+            },
+            "mate": {
+                "type": [ "number", "null" ],
+                "description": "If there is a forced mate line, set this to the amount of moves until mate. Additionally, if the current player is going to get mated, this should be a negative value. For example, if it is currently black to move and black is going to get mated in 3 moves, this should be -3, and if it is white's move and black is going to be mated in 3 moves, this should be 3. If this field is set, the \"eval\" field will be ignored."
+            },
+            "depth": {
+                "type": [ "number", "null" ],
+                "description": "An approximate depth in half-moves you have analysed for. Do not keep track of exact moves you had analysed."
+            }
+        },
+        "required": ["ponder", "eval"]
+    });
+    if options.uci_show_wdl {
+        let props = submit_eval_schema["properties"].as_object_mut().unwrap();
+        for (field, outcome) in [("win", "wins"), ("draw", "draws"), ("loss", "loses")] {
+            props.insert(field.to_string(), json!({
+                "type": [ "integer", "null" ],
+                "description": format!("How likely it is in permille that the side to move {outcome} the game. \"win\", \"draw\" and \"loss\" must add up to exactly 1000.")
+            }));
+        }
+    }
+    if multipv > 1 {
+        submit_eval_schema = json!({
+            "type": "object",
+            "properties": {
+                "lines": {
+                    "type": "array",
+                    "items": submit_eval_schema,
+                    "minItems": multipv,
+                    "maxItems": multipv,
+                    "description": format!("You must output exactly {multipv} different lines, sorted from the best to the worst. Each line must start with a different legal move.")
+                }
+            },
+            "required": ["lines"]
+        });
+    }
+    if options.output_reasoning {
+        let props = submit_eval_schema
+            .get_mut("properties")
+            .unwrap()
+            .as_object_mut()
+            .unwrap();
+        props.insert("reasoning".to_string(), json!({
+            "type": "string",
+            "description": "Explain why you think this is the best move and why the evaluation is what it is"
+        }));
+
+        submit_eval_schema
+            .as_object_mut()
+            .unwrap()
+            .get_mut("required")
+            .unwrap()
+            .as_array_mut()
+            .unwrap()
+            .push("reasoning".into());
+    }
+
+    submit_eval_schema
+}
+
+/// Build the conversation for the query, starting with the given system prompt
+pub fn prompt(query: &Query, system_prompt: &str) -> Option<Vec<Message>> {
+    let options = &query.options;
+    let board = query.game.fen();
+
+    let fen = if options.fenasmd {
+        let fen = fen2md(board.clone());
+        if let Err(fen) = fen {
+            outputln!("info string error: could not parse fen: {fen:?}");
+            return None;
+        }
+        fen.unwrap()
+    } else {
+        "FEN: ".to_string() + &board
+    };
+
+    let mut messages = vec![
+        Message::system(system_prompt),
+        Message::user(fen),
+        Message::user(
+            "Legal moves: ".to_string()
+                + query
+                    .legal_moves
+                    .iter()
+                    .map(|x| x.to_string())
+                    .collect::<Vec<String>>()
+                    .join(", ")
+                    .as_str(),
+        ),
+    ];
+
+    if options.uci_limit_strength {
+        messages.push(Message::system(format!(
+            "Play like a human rated about {} Elo would. Still evaluate the position honestly, \
+             but choose the kind of moves and plans a player of that strength would come up with.",
+            options.uci_elo
+        )));
+    }
+
+    if options.uci_chess960 {
+        messages.push(Message::user(
+            "This is a Chess960 (Fischer Random) game, the pieces on the back ranks started from a shuffled setup. \
+             Castling still puts the king on the g-file (short) or the c-file (long) and the rook right next to it on the f-file or the d-file, \
+             wherever the king and the rook started. The castling field of the FEN names the files of the rooks each side can still castle with. \
+             Castling moves are written as the king taking its own rook, for example g1h1 when the king is on g1 and the castling rook on h1, \
+             and the legal moves list them that way too.",
+        ));
+    }
+
+    if !query.game.moves().is_empty() {
+        let start = match query.game.start_fen() {
+            Some(fen) => format!("the position {fen}"),
+            None => "the standard starting position".to_string(),
+        };
+        messages.push(Message::user(format!(
+            "The game so far, starting from {start}: {}",
+            query.game.history()
+        )));
+    }
+
+    let draws = query
+        .legal_moves
+        .iter()
+        .filter_map(|mov| Some(format!("{mov} ({})", query.game.draw_after(*mov)?)))
+        .collect::<Vec<_>>();
+    if !draws.is_empty() {
+        messages.push(Message::user(format!(
+            "These moves end the game in a draw right away: {}. Only play them if a draw is a good result for the side to move.",
+            draws.join(", ")
+        )));
+    }
+
+    let repeating = query
+        .legal_moves
+        .iter()
+        .filter(|mov| query.game.occurrences_after(**mov) == 1)
+        .map(|mov| mov.to_string())
+        .collect::<Vec<_>>();
+    if !repeating.is_empty() {
+        messages.push(Message::user(format!(
+            "These moves repeat an earlier position of the game, repeating it once more is a draw: {}.",
+            repeating.join(", ")
+        )));
+    }
+
+    if let Some(previous) = &query.previous {
+        messages.push(Message::user(format!(
+            "Your previous analysis of this position was: {previous}. Look deeper into the position and refine it."
+        )));
+    }
+
+    if !options.additional_instructions.is_empty() {
+        messages.push(Message::system(options.additional_instructions.clone()));
+    }
+
+    Some(messages)
+}
+
+/// Deserialize the ai's answer into evaluations and the reasoning, without looking at the board
+pub fn parse_submission(output: &str, multipv: usize) -> Result<(Vec<SubmitEval>, Option<String>), String> {
+    if multipv > 1 {
+        let submitted = serde_json::from_str::<SubmitLines>(output)
+            .map_err(|err| format!("could not parse ai's response: {err}, {output}"))?;
+        Ok((submitted.lines, submitted.reasoning))
+    } else {
+        let eval = serde_json::from_str::<SubmitEval>(output)
+            .map_err(|err| format!("could not parse ai's response: {err}, {output}"))?;
+        let reasoning = eval.reasoning.clone();
+        Ok((vec![eval], reasoning))
+    }
+}
+
+pub fn parse_lines(output: &str, query: &Query) -> Result<Vec<Line>, String> {
+    let (evals, reasoning) = parse_submission(output, multipv(&query.options, &query.legal_moves))?;
+
+    if let Some(exp) = reasoning
+        && !exp.is_empty()
+    {
+        outputln!("info string reasoning: {exp}");
+    }
+
+    let mut lines: Vec<Line> = vec![];
+    for line in evals.into_iter().filter_map(|x| line_from_eval(x, &query.legal_moves, &query.game)) {
+        if lines.iter().any(|x| x.bestmove == line.bestmove) {
+            outputln!("info string error: ai returned {} as the first move of two lines, ignoring the second one", line.bestmove);
+            continue;
+        }
+        lines.push(line);
+    }
+
+    if lines.is_empty() {
+        return Err("ai returned no lines starting with a legal move".into());
+    }
+
+    Ok(lines)
+}
+
+/// Print the lines as info and remember them in case we have to settle for them early
+pub fn report(query: &Query, lines: &[Line]) {
+    if query.sampled {
+        return;
+    }
+    print_lines(&query.options, lines);
+    *query.latest.lock().unwrap() = Some(lines.to_vec());
+}
+
+/// Send the lines to the gui as `info multipv`
+pub fn print_lines(options: &Options, lines: &[Line]) {
+    for (k, line) in lines.iter().enumerate() {
+        let wdl = match options.uci_show_wdl {
+            true => {
+                let [win, draw, loss] = line
+                    .wdl
+                    .unwrap_or_else(|| wdl::from_advantage(line.score.pawns()));
+                format!(" wdl {win} {draw} {loss}")
+            }
+            false => String::new(),
+        };
+        outputln!("info multipv {} depth {} score {}{wdl} pv {}", k + 1, line.depth, line.score, line.pv);
+    }
+}
+
+pub fn client(options: &Options) -> Client<OpenAIConfig> {
+    Client::with_config(
+        OpenAIConfig::new()
+            .with_api_base(options.apibaseurl.clone())
+            .with_api_key(options.apikey.clone()),
+    )
+}
+
+/// Wait for a single api request, giving up on it after `APITimeoutMs` if that is set
+pub async fn with_timeout<T, E: Display>(options: &Options, request: impl Future<Output = Result<T, E>>) -> Result<T, String> {
+    if options.api_timeout_ms == 0 {
+        return request.await.map_err(|err| err.to_string());
+    }
+    match timeout(Duration::from_millis(options.api_timeout_ms as u64), request).await {
+        Ok(res) => res.map_err(|err| err.to_string()),
+        Err(_) => Err(format!("the api didn't answer within {} ms", options.api_timeout_ms)),
+    }
+}
+
+/// Whether requests and responses should be dumped as info strings. They go to the record file instead if there is one
+pub fn debug_dumps(options: &Options) -> bool {
+    options.debug && options.record_file.is_empty()
+}
+
+/// Only compare the position part of the fen, the move clocks don't matter here
+pub fn same_position(a: &str, b: &str) -> bool {
+    a.split_whitespace().take(4).eq(b.split_whitespace().take(4))
+}
+
+pub fn latest_bestmove(latest: &Latest) -> Option<ChessMove> {
+    latest.lock().unwrap().as_ref().map(|lines| lines[0].bestmove)
+}
+
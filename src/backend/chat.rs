@@ -0,0 +1,110 @@
+use async_openai::types::chat::{
+    ChatCompletionRequestMessage, CreateChatCompletionRequestArgs, ResponseFormat,
+    ResponseFormatJsonSchema,
+};
+use tokio::time::Instant;
+
+use crate::backend::record::record;
+use crate::backend::{IBackend, Line, Message, Query, Role, client, debug_dumps, with_timeout, eval_schema, parse_lines, prompt, report};
+use crate::outputln;
+use crate::state::options::SearchMode;
+
+/// The Chat Completions API, for OpenAI-compatible servers that don't implement `/responses`
+pub struct ChatBackend;
+
+fn chat_messages(messages: Vec<Message>) -> Vec<ChatCompletionRequestMessage> {
+    messages
+        .into_iter()
+        .map(|message| match message.role {
+            Role::System => ChatCompletionRequestMessage::System(message.text.into()),
+            Role::User => ChatCompletionRequestMessage::User(message.text.into()),
+        })
+        .collect()
+}
+
+/// Cut the json object out of a plain text answer, models like to wrap it in a code block
+fn extract_json(text: &str) -> &str {
+    match (text.find('{'), text.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &text[start..=end],
+        _ => text,
+    }
+}
+
+impl IBackend for ChatBackend {
+    async fn evaluate(&self, query: Query) -> Option<Vec<Line>> {
+        let options = &query.options;
+        if options.search_mode == SearchMode::Tools {
+            outputln!("info string error: SearchMode Tools is only supported by the Responses backend, falling back to Simple");
+        }
+
+        let submit_eval_schema = eval_schema(options, &query.legal_moves);
+        let mut messages = prompt(&query, include_str!("../assets/go-simple.md"))?;
+
+        let mut req = CreateChatCompletionRequestArgs::default();
+        req.model(options.apimodel.clone());
+
+        if options.chat_json_schema {
+            req.response_format(ResponseFormat::JsonSchema {
+                json_schema: ResponseFormatJsonSchema {
+                    description: Some("Evaluation output schema".into()),
+                    name: "Evaluation_output".into(),
+                    schema: Some(submit_eval_schema),
+                    strict: Some(true),
+                },
+            });
+        } else {
+            // the server can't enforce the schema, so the best we can do is to ask nicely
+            messages.push(Message::system(format!(
+                "Respond with a single JSON object and nothing else. The object must match this \"Evaluation output\" JSON schema: {submit_eval_schema}"
+            )));
+        }
+
+        let req = req.messages(chat_messages(messages)).build().unwrap();
+
+        if options.debug {
+            outputln!("info string going to send off a request now");
+        }
+        if debug_dumps(options) {
+            outputln!(
+                "info string debug going to send this: {}",
+                serde_json::to_string(&req).unwrap()
+            );
+        }
+
+        let request = serde_json::to_value(&req).unwrap();
+        let started = Instant::now();
+        let res = match with_timeout(options, client(options).chat().create(req)).await {
+            Ok(res) => res,
+            Err(err) => {
+                outputln!("info string error network error while fetching response: {err}");
+                record(&query, request, Err(err), started).await;
+                return None;
+            }
+        };
+
+        if debug_dumps(options) {
+            outputln!(
+                "info string debug received response: {}",
+                serde_json::to_string(&res).unwrap()
+            );
+        }
+
+        let Some(output) = res.choices.into_iter().next().and_then(|x| x.message.content) else {
+            record(&query, request, Err("no content in the response".into()), started).await;
+            outputln!("info string error: ai returned no content");
+            return None;
+        };
+        record(&query, request, Ok(extract_json(&output)), started).await;
+
+        match parse_lines(extract_json(&output), &query) {
+            Ok(lines) => {
+                report(&query, &lines);
+                Some(lines)
+            }
+            Err(err) => {
+                outputln!("info string error: {err}");
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use std::time::Duration;
+
+use serde_json::json;
+use tokio::fs;
+use tokio::time::Instant;
+
+use crate::backend::record::record;
+use crate::backend::{IBackend, Line, Query, debug_dumps, multipv, with_timeout, parse_lines, report, same_position};
+use crate::outputln;
+
+/// A backend that never leaves the machine, for testing the engine without an api.
+///
+/// It answers with the raw `MockResponse` if that is set, otherwise with the first matching entry of `MockScriptFile`,
+/// and otherwise with the first legal moves. Every answer goes through the same parsing as a real one.
+pub struct MockBackend;
+
+/// Find the answer for this position in a script.
+///
+/// Each line looks like `<fen> | <answer>`, where the fen can be `*` to match any position.
+/// The answer is passed on as is if it starts with `{`, otherwise it is a space-separated line of moves.
+fn find_in_script(script: &str, fen: &str) -> Option<String> {
+    script
+        .lines()
+        .map(|x| x.trim())
+        .filter(|x| !x.is_empty() && !x.starts_with('#'))
+        .filter_map(|x| x.split_once('|'))
+        .find(|(position, _)| position.trim() == "*" || same_position(position, fen))
+        .map(|(_, answer)| answer.trim().to_string())
+}
+
+fn answer_from_moves(pvs: Vec<Vec<String>>, multipv: usize) -> String {
+    let lines = pvs
+        .into_iter()
+        .map(|ponder| json!({ "ponder": ponder, "eval": 0.0, "mate": null, "depth": 1 }))
+        .collect::<Vec<_>>();
+
+    if multipv > 1 {
+        json!({ "lines": lines }).to_string()
+    } else {
+        lines[0].to_string()
+    }
+}
+
+impl IBackend for MockBackend {
+    async fn evaluate(&self, query: Query) -> Option<Vec<Line>> {
+        let options = &query.options;
+        let multipv = multipv(options, &query.legal_moves);
+        let started = Instant::now();
+
+        if options.mock_latency_ms > 0 {
+            // the latency stands in for the http request, so it times out like one
+            let request = async {
+                tokio::time::sleep(Duration::from_millis(options.mock_latency_ms as u64)).await;
+                Ok::<_, String>(())
+            };
+            if let Err(err) = with_timeout(options, request).await {
+                outputln!("info string error: {err}");
+                record(&query, json!({ "backend": "mock" }), Err(err), started).await;
+                return None;
+            }
+        }
+
+        let mut output = None;
+        if !options.mock_response.is_empty() {
+            // passed on as is, so it can be used to feed the engine malformed answers
+            output = Some(options.mock_response.clone());
+        } else if !options.mock_script_file.is_empty() {
+            match fs::read_to_string(&options.mock_script_file).await {
+                Ok(script) => output = find_in_script(&script, &query.game.fen()),
+                Err(err) => {
+                    outputln!("info string error: couldn't read the mock script {}: {err}", options.mock_script_file);
+                    return None;
+                }
+            }
+        }
+
+        let output = match output {
+            Some(answer) if answer.starts_with('{') || !options.mock_response.is_empty() => answer,
+            Some(answer) => answer_from_moves(vec![answer.split_whitespace().map(|x| x.to_string()).collect()], multipv),
+            None => answer_from_moves(
+                query.legal_moves.iter().take(multipv).map(|x| vec![x.to_string()]).collect(),
+                multipv,
+            ),
+        };
+
+        if debug_dumps(options) {
+            outputln!("info string debug mock backend answered: {output}");
+        }
+        record(&query, json!({ "backend": "mock" }), Ok(&output), started).await;
+
+        match parse_lines(&output, &query) {
+            Ok(lines) => {
+                report(&query, &lines);
+                Some(lines)
+            }
+            Err(err) => {
+                outputln!("info string error: {err}");
+                None
+            }
+        }
+    }
+}
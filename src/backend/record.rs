@@ -0,0 +1,94 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::fs::OpenOptions;
+use tokio::io::AsyncWriteExt;
+use tokio::time::Instant;
+
+use crate::backend::{Query, SubmitEval, multipv, parse_submission};
+use crate::outputln;
+
+/// One api call, written as a line of the `RecordFile`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    /// Unix time in milliseconds
+    pub timestamp: u64,
+    pub fen: String,
+    pub legal_moves: Vec<String>,
+    pub backend: String,
+    pub model: String,
+    pub request: Value,
+    /// The raw text the ai answered with, if the call went through
+    pub output: Option<String>,
+    /// The evaluations parsed out of the output, before checking them against the board
+    pub parsed: Option<Vec<SubmitEval>>,
+    pub error: Option<String>,
+    pub latency_ms: u64,
+    pub retry: u8,
+    /// A turn of a tool session that submitted no evaluation, kept for the log but never replayed
+    #[serde(default)]
+    pub tool_turn: bool,
+}
+
+/// Append the api call to the `RecordFile`, if there is one
+pub async fn record(query: &Query, request: Value, output: Result<&str, String>, started: Instant) {
+    let options = &query.options;
+    if options.record_file.is_empty() {
+        return;
+    }
+
+    let (output, parsed, error) = match output {
+        Ok(output) => match parse_submission(output, multipv(options, &query.legal_moves)) {
+            Ok((evals, _)) => (Some(output.to_string()), Some(evals), None),
+            Err(err) => (Some(output.to_string()), None, Some(err)),
+        },
+        Err(err) => (None, None, Some(err)),
+    };
+    write(query, request, output, parsed, error, started, false).await;
+}
+
+/// Append a tool turn without a submission to the `RecordFile`, if there is one
+pub async fn record_tool_turn(query: &Query, request: Value, output: String, started: Instant) {
+    if query.options.record_file.is_empty() {
+        return;
+    }
+    write(query, request, Some(output), None, None, started, true).await;
+}
+
+async fn write(
+    query: &Query,
+    request: Value,
+    output: Option<String>,
+    parsed: Option<Vec<SubmitEval>>,
+    error: Option<String>,
+    started: Instant,
+    tool_turn: bool,
+) {
+    let options = &query.options;
+    let record = Record {
+        timestamp: SystemTime::now().duration_since(UNIX_EPOCH).map(|x| x.as_millis() as u64).unwrap_or_default(),
+        fen: query.game.fen(),
+        legal_moves: query.legal_moves.iter().map(|x| x.to_string()).collect(),
+        backend: options.backend.to_string(),
+        model: options.apimodel.clone(),
+        request,
+        output,
+        parsed,
+        error,
+        latency_ms: started.elapsed().as_millis() as u64,
+        retry: query.retry,
+        tool_turn,
+    };
+
+    let line = serde_json::to_string(&record).unwrap() + "\n";
+    let file = OpenOptions::new().create(true).append(true).open(&options.record_file).await;
+    let written = match file {
+        Ok(mut file) => file.write_all(line.as_bytes()).await,
+        Err(err) => Err(err),
+    };
+
+    if let Err(err) = written {
+        outputln!("info string error: couldn't write to the record file {}: {err}", options.record_file);
+    }
+}
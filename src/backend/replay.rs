@@ -0,0 +1,77 @@
+use serde_json::json;
+use tokio::fs;
+
+use crate::backend::record::Record;
+use crate::backend::{IBackend, Line, Query, multipv, parse_lines, report, same_position};
+use crate::outputln;
+
+/// Serves the evaluations from a `RecordFile` written earlier instead of asking the ai
+pub struct ReplayBackend;
+
+impl IBackend for ReplayBackend {
+    async fn evaluate(&self, query: Query) -> Option<Vec<Line>> {
+        let options = &query.options;
+        let multipv = multipv(options, &query.legal_moves);
+
+        let records = match fs::read_to_string(&options.replay_file).await {
+            Ok(records) => records,
+            Err(err) => {
+                outputln!("info string error: couldn't read the replay file {}: {err}", options.replay_file);
+                return None;
+            }
+        };
+
+        let fen = query.game.fen();
+        let recorded = records
+            .lines()
+            .filter_map(|x| serde_json::from_str::<Record>(x).ok())
+            .filter(|x| !x.tool_turn && same_position(&x.fen, &fen))
+            .collect::<Vec<_>>();
+
+        // prefer the call made on the same retry, so a recorded failure followed by a success plays out the same way.
+        // a position evaluated more than once (pondering, several threads) replays the last evaluation that went through
+        let mut same_retry = recorded.iter().filter(|x| x.retry == query.retry);
+        let record = same_retry
+            .clone()
+            .rfind(|x| x.parsed.is_some())
+            .or(same_retry.next_back())
+            .or(recorded.iter().rfind(|x| x.parsed.is_some()));
+        let Some(record) = record else {
+            outputln!("info string error: the replay file has no evaluation recorded for this position");
+            return None;
+        };
+
+        let Some(evals) = record.parsed.clone() else {
+            outputln!(
+                "info string error: replaying the call that failed at {}: {}",
+                record.timestamp,
+                record.error.as_deref().unwrap_or("no evaluation")
+            );
+            return None;
+        };
+        let output = if multipv > 1 {
+            json!({ "lines": evals }).to_string()
+        } else {
+            let Some(eval) = evals.first() else {
+                outputln!("info string error: the evaluation recorded at {} has no lines", record.timestamp);
+                return None;
+            };
+            json!(eval).to_string()
+        };
+
+        if options.debug {
+            outputln!("info string debug replaying the evaluation recorded at {}: {output}", record.timestamp);
+        }
+
+        match parse_lines(&output, &query) {
+            Ok(lines) => {
+                report(&query, &lines);
+                Some(lines)
+            }
+            Err(err) => {
+                outputln!("info string error: {err}");
+                None
+            }
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use async_openai::types::responses::{
+    CreateResponseArgs, FunctionCallOutput, FunctionCallOutputItemParam, FunctionTool, InputContent,
+    InputItem, InputMessage, InputParam, InputRole, InputTextContent, Item, MessageItem, OutputItem,
+    ResponseTextParam, Tool, ToolChoiceOptions, ToolChoiceParam,
+};
+use tokio::time::Instant;
+
+use crate::backend::record::{record, record_tool_turn};
+use crate::backend::{IBackend, Line, Message, Query, Role, client, debug_dumps, with_timeout, eval_schema, parse_lines, prompt, report, tools};
+use crate::outputln;
+use crate::state::options::SearchMode;
+
+/// The OpenAI Responses API
+pub struct ResponsesBackend;
+
+impl IBackend for ResponsesBackend {
+    async fn evaluate(&self, query: Query) -> Option<Vec<Line>> {
+        match query.options.search_mode {
+            SearchMode::Simple => try_get_bestmove(query).await,
+            SearchMode::Tools => try_get_bestmove_tools(query).await,
+        }
+    }
+}
+
+fn input_items(messages: Vec<Message>) -> Vec<InputItem> {
+    messages
+        .into_iter()
+        .map(|message| {
+            InputItem::Item(Item::Message(MessageItem::Input(InputMessage {
+                content: vec![InputContent::InputText(InputTextContent { text: message.text })],
+                role: match message.role {
+                    Role::System => InputRole::System,
+                    Role::User => InputRole::User,
+                },
+                status: None,
+            })))
+        })
+        .collect()
+}
+
+async fn try_get_bestmove(query: Query) -> Option<Vec<Line>> {
+    let options = &query.options;
+    let submit_eval_schema = eval_schema(options, &query.legal_moves);
+    let input_data = input_items(prompt(&query, include_str!("../assets/go-simple.md"))?);
+
+    if options.debug {
+        outputln!("info string going to send off a request now");
+    }
+
+    let req = CreateResponseArgs::default()
+        .model(options.apimodel.clone())
+        .input(InputParam::Items(input_data.clone()))
+        .text(ResponseTextParam {
+            format: async_openai::types::responses::TextResponseFormatConfiguration::JsonSchema(
+                async_openai::types::responses::ResponseFormatJsonSchema {
+                    description: Some("Evaluation output schema".into()),
+                    name: "Evaluation output".into(),
+                    schema: Some(submit_eval_schema),
+                    strict: Some(true),
+                },
+            ),
+            verbosity: Some(async_openai::types::responses::Verbosity::Low),
+        })
+        .build()
+        .unwrap();
+
+    if debug_dumps(options) {
+        outputln!(
+            "info string debug going to send this: {}",
+            serde_json::to_string(&req).unwrap()
+        );
+    }
+
+    let request = serde_json::to_value(&req).unwrap();
+    let started = Instant::now();
+    let res = with_timeout(options, client(options).responses().create(req)).await;
+    if let Err(res) = res {
+        outputln!("info string error network error while fetching response: {res}");
+        record(&query, request, Err(res), started).await;
+        return None;
+    }
+
+    let res = res.unwrap();
+
+    if debug_dumps(options) {
+        outputln!(
+            "info string debug received response: {}",
+            serde_json::to_string(&res).unwrap()
+        );
+    }
+
+    let Some(output) = res.output_text() else {
+        record(&query, request, Err("no text in the response".into()), started).await;
+        outputln!("info string error: ai returned no text");
+        return None;
+    };
+    record(&query, request, Ok(&output), started).await;
+
+    match parse_lines(&output, &query) {
+        Ok(lines) => {
+            report(&query, &lines);
+            Some(lines)
+        }
+        Err(err) => {
+            outputln!("info string error: {err}");
+            None
+        }
+    }
+}
+
+/// Run the multi-turn loop from `go-mcp.md`, where the ai keeps calling `submit_eval` until it is satisfied
+async fn try_get_bestmove_tools(query: Query) -> Option<Vec<Line>> {
+    let options = &query.options;
+    let mut input_data = input_items(prompt(&query, include_str!("../assets/go-mcp.md"))?);
+
+    let mut tools = vec![Tool::Function(FunctionTool {
+        name: "submit_eval".into(),
+        parameters: Some(eval_schema(options, &query.legal_moves)),
+        strict: Some(false),
+        description: Some("Submit your most recent evaluation of the position. The last submission is used as the best move.".into()),
+    })];
+    tools.extend(tools::definitions());
+
+    let client = client(options);
+    let mut submitted = None;
+
+    for turn in 1..=options.apimaxturns {
+        let req = CreateResponseArgs::default()
+            .model(options.apimodel.clone())
+            .input(InputParam::Items(input_data.clone()))
+            .tools(tools.clone())
+            .tool_choice(ToolChoiceParam::Mode(ToolChoiceOptions::Auto))
+            .build()
+            .unwrap();
+
+        if debug_dumps(options) {
+            outputln!(
+                "info string debug going to send this (turn {turn}): {}",
+                serde_json::to_string(&req).unwrap()
+            );
+        }
+
+        let request = serde_json::to_value(&req).unwrap();
+        let started = Instant::now();
+        let res = match with_timeout(options, client.responses().create(req)).await {
+            Ok(res) => res,
+            Err(err) => {
+                outputln!("info string error network error while fetching response (turn {turn}): {err}");
+                record(&query, request, Err(err), started).await;
+                break;
+            }
+        };
+
+        // submissions are recorded as they are so they can be replayed, a turn without one as the whole output
+        let submissions = res
+            .output
+            .iter()
+            .filter_map(|x| match x {
+                OutputItem::FunctionCall(call) if call.name == "submit_eval" => Some(call.arguments.clone()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        if submissions.is_empty() {
+            record_tool_turn(&query, request.clone(), serde_json::to_string(&res.output).unwrap(), started).await;
+        }
+        for submission in submissions {
+            record(&query, request.clone(), Ok(&submission), started).await;
+        }
+
+        if debug_dumps(options) {
+            outputln!(
+                "info string debug received response: {}",
+                serde_json::to_string(&res).unwrap()
+            );
+        }
+
+        let mut calls = vec![];
+        for item in res.output {
+            match item {
+                OutputItem::FunctionCall(call) => calls.push(call),
+                OutputItem::Reasoning(reasoning) => input_data.push(InputItem::Item(Item::Reasoning(reasoning))),
+                OutputItem::Message(message) => input_data.push(InputItem::Item(Item::Message(MessageItem::Output(message)))),
+                _ => {}
+            }
+        }
+
+        // the ai responds with anything but a tool call when it is done
+        if calls.is_empty() {
+            break;
+        }
+
+        for call in calls {
+            let output = if call.name == "submit_eval" {
+                match parse_lines(&call.arguments, &query) {
+                    Ok(lines) => {
+                        report(&query, &lines);
+                        submitted = Some(lines);
+                        "Evaluation accepted. Keep analysing and submit again, or respond with a message to finish.".to_string()
+                    }
+                    Err(err) => {
+                        outputln!("info string error: {err}");
+                        format!("Evaluation rejected: {err}")
+                    }
+                }
+            } else {
+                let output = tools::call(&query.game, &call.name, &call.arguments)
+                    .unwrap_or_else(|| format!("Unknown tool: {}", call.name));
+                if options.debug {
+                    outputln!("info string debug tool call {}({}) returned: {}", call.name, call.arguments, output.replace('\n', " "));
+                }
+                output
+            };
+
+            let call_id = call.call_id.clone();
+            input_data.push(InputItem::Item(Item::FunctionCall(call)));
+            input_data.push(InputItem::Item(Item::FunctionCallOutput(FunctionCallOutputItemParam {
+                call_id,
+                output: FunctionCallOutput::Text(output),
+                id: None,
+                status: None,
+            })));
+        }
+    }
+
+    if submitted.is_none() {
+        outputln!("info string error: ai finished without submitting an evaluation");
+    }
+
+    submitted
+}
+
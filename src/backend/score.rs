@@ -0,0 +1,95 @@
+use std::fmt::Display;
+
+use chess::Color;
+
+use crate::outputln;
+
+/// The largest centipawn score that is reported, anything beyond it is clamped
+const MAX_CP: i32 = 10000;
+
+/// The longest mate that is reported, anything beyond it is clamped
+const MAX_MATE: i32 = 100;
+
+/// Pawns a mate is worth when comparing it to a centipawn score
+const MATE_PAWNS: f32 = 100.0;
+
+/// A UCI score, from the side to move's point of view
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Score {
+    Cp(i32),
+    /// Mate in this many moves, negative if the side to move is getting mated
+    Mate(i32),
+}
+
+impl Score {
+    /// Convert the ai's eval, in pawns from white's point of view, or its mate, already from the side to move's point of view
+    pub fn from_eval(eval: f32, mate: Option<f32>, side: Color) -> Self {
+        if let Some(mate) = mate
+            && mate != 0.0
+        {
+            // mate in half a move is still mate in one
+            let moves = (mate.abs().round() as i32).max(1);
+            if moves > MAX_MATE {
+                outputln!("info string error: ai's mate in {mate} is out of range, clamping it to {MAX_MATE}");
+            }
+            return Self::Mate(moves.min(MAX_MATE) * mate.signum() as i32);
+        }
+
+        let cp = match side {
+            Color::White => eval * 100.0,
+            Color::Black => -eval * 100.0,
+        };
+        if cp.abs() > MAX_CP as f32 {
+            outputln!("info string error: ai's eval of {eval} pawns is out of range, clamping it to {} pawns", MAX_CP / 100);
+        }
+        Self::Cp((cp.round() as i32).clamp(-MAX_CP, MAX_CP))
+    }
+
+    /// How many pawns the side to move is ahead
+    pub fn pawns(&self) -> f32 {
+        match self {
+            Self::Cp(cp) => *cp as f32 / 100.0,
+            Self::Mate(moves) => moves.signum() as f32 * MATE_PAWNS,
+        }
+    }
+}
+
+impl Display for Score {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Cp(cp) => write!(f, "cp {cp}"),
+            Self::Mate(moves) => write!(f, "mate {moves}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eval_is_turned_to_the_side_to_move() {
+        assert_eq!(Score::from_eval(1.25, None, Color::White), Score::Cp(125));
+        assert_eq!(Score::from_eval(1.25, None, Color::Black), Score::Cp(-125));
+        assert_eq!(Score::from_eval(-0.3, None, Color::Black), Score::Cp(30));
+    }
+
+    #[test]
+    fn mate_is_already_from_the_side_to_move() {
+        assert_eq!(Score::from_eval(0.0, Some(3.0), Color::Black), Score::Mate(3));
+        assert_eq!(Score::from_eval(5.0, Some(-2.0), Color::White), Score::Mate(-2));
+        assert_eq!(Score::from_eval(0.0, Some(0.4), Color::White), Score::Mate(1));
+    }
+
+    #[test]
+    fn mate_zero_means_no_mate() {
+        assert_eq!(Score::from_eval(0.5, Some(0.0), Color::White), Score::Cp(50));
+    }
+
+    #[test]
+    fn out_of_range_scores_are_clamped() {
+        assert_eq!(Score::from_eval(1000.0, None, Color::White), Score::Cp(MAX_CP));
+        assert_eq!(Score::from_eval(1000.0, None, Color::Black), Score::Cp(-MAX_CP));
+        assert_eq!(Score::from_eval(0.0, Some(-500.0), Color::White), Score::Mate(-MAX_MATE));
+    }
+}
@@ -0,0 +1,133 @@
+use std::fmt::Write;
+use std::str::FromStr;
+
+use async_openai::types::responses::{FunctionTool, Tool};
+use chess::{Board, BoardStatus, ChessMove, Color, Piece, ALL_PIECES};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::state::game::Game;
+
+/// Arguments every board tool takes: a line of moves played from the position being evaluated
+#[derive(Debug, Clone, Deserialize)]
+struct LineArgs {
+    moves: Vec<String>,
+}
+
+fn tool(name: &str, description: &str) -> Tool {
+    Tool::Function(FunctionTool {
+        name: name.into(),
+        parameters: Some(json!({
+            "type": "object",
+            "properties": {
+                "moves": {
+                    "type": "array",
+                    "items": { "type": "string" },
+                    "description": "Moves in UCI long algebraic notation played from the position you are evaluating, for example [\"e2e4\",\"e7e5\"]. Pass an empty array to ask about the position itself."
+                }
+            },
+            "required": ["moves"]
+        })),
+        strict: Some(false),
+        description: Some(description.into()),
+    })
+}
+
+/// The board tools the ai can call during a tool search, next to `submit_eval`
+pub fn definitions() -> Vec<Tool> {
+    vec![
+        tool("legal_moves", "List the legal moves in the position after the given line."),
+        tool("is_legal", "Check whether every move of the given line is legal."),
+        tool("fen_after", "Get the FEN of the position after the given line."),
+        tool("material", "Count the material of both sides after the given line."),
+        tool("in_check", "Tell whether the side to move is in check, checkmated or stalemated after the given line."),
+    ]
+}
+
+pub fn piece_value(piece: Piece) -> u32 {
+    match piece {
+        Piece::Pawn => 1,
+        Piece::Knight => 3,
+        Piece::Bishop => 3,
+        Piece::Rook => 5,
+        Piece::Queen => 9,
+        Piece::King => 0,
+    }
+}
+
+/// Material count of the given side in pawns, not counting the king
+pub fn material(board: &Board, color: Color) -> u32 {
+    ALL_PIECES
+        .iter()
+        .map(|piece| (board.pieces(*piece) & board.color_combined(color)).popcnt() * piece_value(*piece))
+        .sum()
+}
+
+fn play(mut game: Game, moves: &[String]) -> Result<Game, String> {
+    for (i, mov) in moves.iter().enumerate() {
+        let chess_move = ChessMove::from_str(mov).map_err(|_| format!("move {} ({mov}) is not a valid UCI move", i + 1))?;
+        if !game.is_legal(chess_move) {
+            return Err(format!("move {} ({mov}) is not legal in the position {}", i + 1, game.fen()));
+        }
+        game.push(chess_move);
+    }
+    Ok(game)
+}
+
+/// Run a board tool. Returns `None` if there is no tool with this name
+pub fn call(game: &Game, name: &str, arguments: &str) -> Option<String> {
+    if !matches!(name, "legal_moves" | "is_legal" | "fen_after" | "material" | "in_check") {
+        return None;
+    }
+
+    let args: LineArgs = match serde_json::from_str(arguments) {
+        Ok(args) => args,
+        Err(err) => return Some(format!("Could not parse the arguments: {err}")),
+    };
+
+    let game = match play(game.clone(), &args.moves) {
+        Ok(game) => game,
+        Err(err) if name == "is_legal" => return Some(format!("No, {err}")),
+        Err(err) => return Some(format!("The line is illegal: {err}")),
+    };
+
+    let board = game.board();
+    let output = match name {
+        "legal_moves" => {
+            let moves = game.legal_moves().iter().map(|x| x.to_string()).collect::<Vec<String>>();
+            if moves.is_empty() {
+                "There are no legal moves in this position".to_string()
+            } else {
+                moves.join(", ")
+            }
+        }
+        "is_legal" => "Yes, every move of this line is legal".to_string(),
+        "fen_after" => game.fen(),
+        "material" => {
+            let (white, black) = (material(&board, Color::White), material(&board, Color::Black));
+            let mut output = format!("White's material count: {white}\nBlack's material count: {black}\n");
+            match white.cmp(&black) {
+                std::cmp::Ordering::Equal => write!(output, "The players are equal on material"),
+                std::cmp::Ordering::Greater => write!(output, "White has a +{} material advantage", white - black),
+                std::cmp::Ordering::Less => write!(output, "Black has a -{} material advantage", black - white),
+            }
+            .unwrap();
+            output
+        }
+        "in_check" => {
+            let side = match board.side_to_move() {
+                Color::White => "White",
+                Color::Black => "Black",
+            };
+            match board.status() {
+                BoardStatus::Checkmate => format!("{side} is checkmated"),
+                BoardStatus::Stalemate => format!("{side} is stalemated"),
+                BoardStatus::Ongoing if board.checkers().popcnt() > 0 => format!("{side} is in check"),
+                BoardStatus::Ongoing => format!("{side} is not in check"),
+            }
+        }
+        _ => unreachable!(),
+    };
+
+    Some(output)
+}
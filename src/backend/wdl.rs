@@ -0,0 +1,30 @@
+/// How quickly the win and loss chances grow with the advantage, per pawn
+const STEEPNESS: f32 = 1.2;
+
+/// The advantage in pawns at which winning becomes as likely as not
+const WINNING_ADVANTAGE: f32 = 1.0;
+
+/// Check the win/draw/loss permille the ai submitted
+pub fn validate(win: f32, draw: f32, loss: f32) -> Result<[u32; 3], String> {
+    let wdl = [win, draw, loss];
+    if wdl.iter().any(|x| !(0.0..=1000.0).contains(x)) {
+        return Err(format!("wdl {win} {draw} {loss} has values outside of 0-1000"));
+    }
+
+    let wdl = wdl.map(|x| x.round() as u32);
+    if wdl.iter().sum::<u32>() != 1000 {
+        return Err(format!("wdl {} {} {} doesn't add up to 1000", wdl[0], wdl[1], wdl[2]));
+    }
+
+    Ok(wdl)
+}
+
+/// Estimate the win/draw/loss permille from how many pawns the side to move is ahead
+pub fn from_advantage(advantage: f32) -> [u32; 3] {
+    let win = 1.0 / (1.0 + (-(advantage - WINNING_ADVANTAGE) * STEEPNESS).exp());
+    let loss = 1.0 / (1.0 + ((advantage + WINNING_ADVANTAGE) * STEEPNESS).exp());
+
+    let win = (win * 1000.0).round() as u32;
+    let loss = (loss * 1000.0).round() as u32;
+    [win, 1000 - win - loss, loss]
+}
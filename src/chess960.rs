@@ -0,0 +1,290 @@
+//! Chess960 castling.
+//!
+//! The `chess` crate only knows the standard castling setup, so the boards of a Chess960 game have no castling rights
+//! of their own. The rights are kept next to them instead, as the files of the rooks each side can still castle with,
+//! and castling moves are generated and played here. They are written the way Chess960 GUIs expect them, as the king
+//! taking its own rook.
+
+use std::str::FromStr;
+
+use chess::{
+    BitBoard, Board, BoardBuilder, ChessMove, Color, EMPTY, File, Piece, Rank, Square, between, get_bishop_moves,
+    get_king_moves, get_knight_moves, get_pawn_attacks, get_rook_moves,
+};
+
+const SHORT: usize = 0;
+const LONG: usize = 1;
+
+/// The files of the rooks each side can still castle with, indexed by color and then short before long
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Castling([[Option<File>; 2]; 2]);
+
+/// The pieces of a rank (1-8) in the placement part of a fen, indexed by file
+fn rank_pieces(placement: &str, rank: usize) -> [Option<char>; 8] {
+    let mut pieces = [None; 8];
+    let Some(row) = placement.split('/').nth(8 - rank) else {
+        return pieces;
+    };
+
+    let mut file = 0;
+    for chr in row.chars() {
+        if let Some(empty) = chr.to_digit(10) {
+            file += empty as usize;
+        } else if file < 8 {
+            pieces[file] = Some(chr);
+            file += 1;
+        }
+    }
+    pieces
+}
+
+/// Find the king and rook files a castling right from a Shredder-FEN or X-FEN castling field refers to
+pub fn castling_files(placement: &str, right: char) -> Option<(usize, usize)> {
+    let white = right.is_ascii_uppercase();
+    let pieces = rank_pieces(placement, if white { 1 } else { 8 });
+    let (king, rook) = if white { ('K', 'R') } else { ('k', 'r') };
+
+    let king_file = pieces.iter().position(|x| *x == Some(king))?;
+    let rooks = (0..8).filter(|x| pieces[*x] == Some(rook));
+
+    let rook_file = match right.to_ascii_lowercase() {
+        // X-FEN: the outermost rook on that side of the king
+        'k' => rooks.filter(|x| *x > king_file).max()?,
+        'q' => rooks.filter(|x| *x < king_file).min()?,
+        // Shredder-FEN: the file of the rook
+        file @ 'a'..='h' => {
+            let file = file as usize - 'a' as usize;
+            pieces[file].filter(|x| *x == rook && file != king_file)?;
+            file
+        }
+        _ => return None,
+    };
+
+    Some((king_file, rook_file))
+}
+
+/// Parse a fen with a Shredder-FEN, X-FEN or `KQkq` castling field into a board without castling rights and the
+/// rights themselves
+pub fn parse_fen(fen: &str) -> Result<(Board, Castling), String> {
+    let mut fields = fen.split_whitespace().map(|x| x.to_string()).collect::<Vec<_>>();
+    if fields.len() < 3 {
+        return Board::from_str(fen).map(|board| (board, Castling::default())).map_err(|err| format!("{err}"));
+    }
+
+    let mut castling = Castling::default();
+    for right in fields[2].chars().filter(|x| *x != '-') {
+        let Some((king, rook)) = castling_files(&fields[0], right) else {
+            return Err(format!("castling right {right} has no king and rook to castle with"));
+        };
+        let color = if right.is_ascii_uppercase() { Color::White } else { Color::Black };
+        let side = if rook > king { SHORT } else { LONG };
+        castling.0[color.to_index()][side] = Some(File::from_index(rook));
+    }
+
+    fields[2] = "-".into();
+    let board = Board::from_str(&fields.join(" ")).map_err(|err| format!("{err}"))?;
+    Ok((board, castling))
+}
+
+fn back_rank(color: Color) -> Rank {
+    match color {
+        Color::White => Rank::First,
+        Color::Black => Rank::Eighth,
+    }
+}
+
+/// Where the king and the rook end up, which is the same as in standard chess
+fn destinations(color: Color, side: usize) -> (Square, Square) {
+    let rank = back_rank(color);
+    match side {
+        SHORT => (Square::make_square(rank, File::G), Square::make_square(rank, File::F)),
+        _ => (Square::make_square(rank, File::C), Square::make_square(rank, File::D)),
+    }
+}
+
+/// Whether `by` attacks the square, with the given pieces standing in the way
+fn attacked(board: &Board, square: Square, by: Color, occupied: BitBoard) -> bool {
+    let pieces = |piece| *board.pieces(piece) & *board.color_combined(by);
+    let diagonal = pieces(Piece::Bishop) | pieces(Piece::Queen);
+    let straight = pieces(Piece::Rook) | pieces(Piece::Queen);
+
+    get_bishop_moves(square, occupied) & diagonal != EMPTY
+        || get_rook_moves(square, occupied) & straight != EMPTY
+        || get_knight_moves(square) & pieces(Piece::Knight) != EMPTY
+        || get_king_moves(square) & pieces(Piece::King) != EMPTY
+        || get_pawn_attacks(square, !by, pieces(Piece::Pawn)) != EMPTY
+}
+
+/// Whether the move is the king taking its own rook, which is how castling moves are written
+pub fn is_castle(board: &Board, mov: ChessMove) -> bool {
+    let side = board.side_to_move();
+    board.piece_on(mov.get_source()) == Some(Piece::King)
+        && board.piece_on(mov.get_dest()) == Some(Piece::Rook)
+        && board.color_on(mov.get_source()) == Some(side)
+        && board.color_on(mov.get_dest()) == Some(side)
+}
+
+/// Play a castling move, which the `chess` crate can't do for us
+pub fn castle(board: &Board, mov: ChessMove) -> Board {
+    let color = board.side_to_move();
+    let (king, rook) = (mov.get_source(), mov.get_dest());
+    let side = if rook.get_file() > king.get_file() { SHORT } else { LONG };
+    let (king_to, rook_to) = destinations(color, side);
+
+    let mut builder = BoardBuilder::from(board);
+    builder
+        .clear_square(king)
+        .clear_square(rook)
+        .piece(king_to, Piece::King, color)
+        .piece(rook_to, Piece::Rook, color)
+        .side_to_move(!color)
+        .en_passant(None);
+    Board::try_from(builder).unwrap()
+}
+
+impl Castling {
+    /// The castling moves the side to move can legally play
+    pub fn moves(self, board: &Board) -> Vec<ChessMove> {
+        let color = board.side_to_move();
+        let king = board.king_square(color);
+        if *board.checkers() != EMPTY || king.get_rank() != back_rank(color) {
+            return vec![];
+        }
+
+        let mut moves = vec![];
+        for (side, rook) in self.0[color.to_index()].iter().enumerate() {
+            let Some(rook) = rook.map(|x| Square::make_square(back_rank(color), x)) else {
+                continue;
+            };
+            let (king_to, rook_to) = destinations(color, side);
+
+            // every square the king and the rook go through or to has to be empty, but for the two of them
+            let others = *board.combined() ^ BitBoard::from_square(king) ^ BitBoard::from_square(rook);
+            let path = between(king, king_to) | between(rook, rook_to) | BitBoard::from_square(king_to) | BitBoard::from_square(rook_to);
+            if path & others != EMPTY {
+                continue;
+            }
+
+            // the king can't pass through an attacked square, nor end up in check
+            let without_king = *board.combined() ^ BitBoard::from_square(king);
+            if between(king, king_to).any(|x| attacked(board, x, !color, without_king)) {
+                continue;
+            }
+            let after = others | BitBoard::from_square(king_to) | BitBoard::from_square(rook_to);
+            if attacked(board, king_to, !color, after) {
+                continue;
+            }
+
+            moves.push(ChessMove::new(king, rook, None));
+        }
+        moves
+    }
+
+    /// The rights that are left after the move, moving the king or a rook or taking a rook gives them up
+    pub fn after(self, board: &Board, mov: ChessMove) -> Self {
+        let mut castling = self;
+        if board.piece_on(mov.get_source()) == Some(Piece::King) {
+            castling.0[board.side_to_move().to_index()] = [None; 2];
+        }
+
+        for color in [Color::White, Color::Black] {
+            for rook in castling.0[color.to_index()].iter_mut() {
+                let square = rook.map(|x| Square::make_square(back_rank(color), x));
+                if square == Some(mov.get_source()) || square == Some(mov.get_dest()) {
+                    *rook = None;
+                }
+            }
+        }
+        castling
+    }
+
+    /// The rights as the castling field of a Shredder-FEN, for example `HAha`
+    pub fn fen_field(self) -> String {
+        let mut field = String::new();
+        for color in [Color::White, Color::Black] {
+            for rook in self.0[color.to_index()].iter().flatten() {
+                let file = (b'a' + rook.to_index() as u8) as char;
+                field.push(if color == Color::White { file.to_ascii_uppercase() } else { file });
+            }
+        }
+
+        if field.is_empty() {
+            field.push('-');
+        }
+        field
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mov(uci: &str) -> ChessMove {
+        ChessMove::from_str(uci).unwrap()
+    }
+
+    #[test]
+    fn shredder_and_x_fen_rights_name_the_same_rooks() {
+        let (board, shredder) = parse_fen("bqnbrk1r/pppppppp/8/8/8/8/PPPPPPPP/BQNBRK1R w HEhe - 0 1").unwrap();
+        let (_, x_fen) = parse_fen("bqnbrk1r/pppppppp/8/8/8/8/PPPPPPPP/BQNBRK1R w KQkq - 0 1").unwrap();
+        assert_eq!(shredder, x_fen);
+        assert_eq!(shredder.fen_field(), "HEhe");
+        assert_eq!(board.castle_rights(Color::White), chess::CastleRights::NoRights);
+    }
+
+    #[test]
+    fn x_fen_picks_the_outermost_rook() {
+        let (_, castling) = parse_fen("4k3/8/8/8/8/8/8/R1R1K1RR w KQ - 0 1").unwrap();
+        assert_eq!(castling.fen_field(), "HA");
+    }
+
+    #[test]
+    fn rights_without_a_rook_are_rejected() {
+        assert!(parse_fen("4k3/8/8/8/8/8/8/4K2R w G - 0 1").is_err());
+        assert!(parse_fen("4k3/8/8/8/8/8/8/4K3 w K - 0 1").is_err());
+    }
+
+    #[test]
+    fn castling_needs_the_squares_between_to_be_empty() {
+        // the h-rook would have to go to f1, where the other rook is
+        let (board, castling) = parse_fen("bqnb1rkr/pppppppp/8/8/8/8/PPPPPPPP/BQNB1RKR w HFhf - 0 1").unwrap();
+        assert_eq!(castling.moves(&board), vec![]);
+
+        let (board, castling) = parse_fen("bqnbrk1r/pppppppp/8/8/8/8/PPPPPPPP/BQNBRK1R w HEhe - 0 1").unwrap();
+        assert_eq!(castling.moves(&board), vec![mov("f1h1")]);
+    }
+
+    #[test]
+    fn the_king_can_castle_without_moving() {
+        let (board, castling) = parse_fen("1r4k1/8/8/8/8/8/8/6KR w H - 0 1").unwrap();
+        assert_eq!(castling.moves(&board), vec![mov("g1h1")]);
+        assert_eq!(castle(&board, mov("g1h1")).to_string(), "1r4k1/8/8/8/8/8/8/5RK1 b - - 0 1");
+    }
+
+    #[test]
+    fn the_king_cannot_castle_through_or_into_check() {
+        let (board, castling) = parse_fen("r3k3/8/8/8/8/8/8/1K5R w H - 0 1").unwrap();
+        assert_eq!(castling.moves(&board), vec![mov("b1h1")]);
+
+        let (board, castling) = parse_fen("4kr2/8/8/8/8/8/8/1K5R w H - 0 1").unwrap();
+        assert_eq!(castling.moves(&board), vec![]);
+
+        let (board, castling) = parse_fen("4k1r1/8/8/8/8/8/8/1K5R w H - 0 1").unwrap();
+        assert_eq!(castling.moves(&board), vec![]);
+    }
+
+    #[test]
+    fn long_castling_puts_the_king_on_c_and_the_rook_on_d() {
+        let (board, castling) = parse_fen("4k3/8/8/8/8/8/8/RK6 w A - 0 1").unwrap();
+        assert_eq!(castling.moves(&board), vec![mov("b1a1")]);
+        assert_eq!(castle(&board, mov("b1a1")).to_string(), "4k3/8/8/8/8/8/8/2KR4 b - - 0 1");
+    }
+
+    #[test]
+    fn moving_the_king_or_a_rook_gives_up_the_rights() {
+        let (board, castling) = parse_fen("r3k2r/8/8/8/8/8/8/R3K2R w HAha - 0 1").unwrap();
+        assert_eq!(castling.after(&board, mov("h1h5")).fen_field(), "Aha");
+        assert_eq!(castling.after(&board, mov("a1a8")).fen_field(), "Hh");
+        assert_eq!(castling.after(&board, mov("e1d1")).fen_field(), "ha");
+    }
+}
@@ -1,36 +1,33 @@
-use async_openai::config::OpenAIConfig;
-use rand::rng;
-use rand::seq::SliceRandom;
-use std::str::FromStr;
-
-use async_openai::Client;
-use async_openai::types::responses::{
-    CreateResponseArgs, InputContent, InputItem, InputMessage, InputParam, InputTextContent,
-    ResponseTextParam,
-};
-use chess::{Board, ChessMove, MoveGen};
-use serde::{Deserialize, Serialize};
-use serde_json::json;
+use std::future::pending;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chess::ChessMove;
+use futures::future::join_all;
+use tokio::time::{Instant, sleep_until, timeout};
 use tokio_util::sync::CancellationToken;
 
+use crate::backend::{Backend, Cache, IBackend, Latest, Line, Query, cache_key, latest_bestmove, print_lines, report};
+use crate::command::go::draws::override_draws;
+use crate::command::go::strength::weaken;
+use crate::command::go::timeman::TimeControl;
+use crate::command::go::vote::{leader, vote};
 use crate::command::{CommandResult, ICommand};
-use crate::fen2md::fen2md;
 use crate::outputln;
-use crate::state::options::Options;
-use crate::state::{GoStoppedNotification, State};
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SubmitEval {
-    ponder: Vec<String>,
-    eval: f32,
-    mate: Option<f32>,
-    depth: Option<f32>,
-    reasoning: Option<String>,
-}
+use crate::state::game::Game;
+use crate::state::options::{DrawMoves, Options};
+use crate::state::State;
+
+mod draws;
+mod fallback;
+mod ensemble;
+mod strength;
+mod timeman;
+mod vote;
 
 pub struct GoCommand;
 
-fn process_keyword(args: &Vec<String>, keyword: String) -> Option<String> {
+fn process_keyword(args: &[String], keyword: String) -> Option<String> {
     let keywords = vec![
         "searchmoves",
         "ponder",
@@ -57,309 +54,347 @@ fn process_keyword(args: &Vec<String>, keyword: String) -> Option<String> {
             if keywords.iter().find(|x| arg == **x).is_some() {
                 break;
             }
-            value = value + &arg + " ";
+            value = value + arg + " ";
         }
     }
 
-    if value == "" {
+    if value.is_empty() {
         None
     } else {
         Some(value.trim_end_matches(" ").to_string())
     }
 }
 
-fn legal_moves(board: Board) -> Vec<ChessMove> {
-    MoveGen::new_legal(&board).collect()
-}
-
-fn best(mov: ChessMove) {
-    outputln!("bestmove {mov}");
+fn has_keyword(args: &[String], keyword: &str) -> bool {
+    args.iter().any(|x| x == keyword)
 }
 
-async fn try_get_bestmove(
-    options: Options,
-    board: Board,
-    legal_moves: Vec<ChessMove>,
-) -> Option<(ChessMove, String, String, u32)> {
-    let mut submit_eval_schema = json!({
-        "type": "object",
-        "properties": {
-            "ponder": {
-                "type": "array",
-                "items": {
-                    "type": "string",
-                    "enum": legal_moves.iter().map(|x| x.to_string()).collect::<Vec<String>>()
-                },
-                "minItems": 1,
-                "description": "This is the list of moves you think is the best line. For example, if you think that the best line from a starting position is 1. d4 d5, this field should be [\"d2d4\",\"d7d5\"], provided \"d2d4\" is one of the legal moves."
-            },
-            "eval": {
-                "type": "number",
-                "description": "The evalutaion score in centipawns. For example, if this is 0.9, white has a 0.9 pawn in advantage and if it is -1, black has a 1 pawn advantage, and if this is 0, the game is equal."
-            },
-            "mate": {
-                "type": [ "number", "null" ],
-                "description": "If there is a forced mate line, set this to the amount of moves until mate. Additionally, if the current player is going to get mated, this should be a negative value. For example, if it is currently black to move and black is going to get mated in 3 moves, this should be -3, and if it is white's move and black is going to be mated in 3 moves, this should be 3. If this field is set, the \"eval\" field will be ignored."
-            },
-            "depth": {
-                "type": [ "number", "null" ],
-                "description": "An approximate depth in half-moves you have analysed for. Do not keep track of exact moves you had analysed."
+/// Narrow down the legal moves to the ones the gui asked for with `go searchmoves`
+fn restrict_to_searchmoves(legal_moves: Vec<ChessMove>, searchmoves: &str) -> Vec<ChessMove> {
+    let mut restricted = vec![];
+    for mov in searchmoves.split_whitespace() {
+        match legal_moves.iter().find(|x| x.to_string() == mov) {
+            Some(legal) => {
+                if !restricted.contains(legal) {
+                    restricted.push(*legal);
+                }
+            }
+            None => {
+                outputln!("info string error: searchmoves contains {mov}, which is not a legal move");
             }
-        },
-        "required": ["ponder", "eval"]
-    });
-    if options.output_reasoning {
-        let props = submit_eval_schema
-            .get_mut("properties")
-            .unwrap()
-            .as_object_mut()
-            .unwrap();
-        props.insert("reasoning".to_string(), json!({
-            "type": "string",
-            "description": "Explain why you think this is the best move and why the evaluation is what it is"
-        }));
-
-        submit_eval_schema
-            .as_object_mut()
-            .unwrap()
-            .get_mut("required")
-            .unwrap()
-            .as_array_mut()
-            .unwrap()
-            .push("reasoning".into());
-    }
-
-    let fen = if options.fenasmd {
-        let fen = fen2md(board.to_string());
-        if let Err(fen) = fen {
-            outputln!("info string error: could not parse fen: {fen:?}");
-            return None;
         }
-        fen.unwrap()
-    } else {
-        "FEN: ".to_string() + &board.to_string()
-    };
+    }
 
-    if options.debug {
-        outputln!("info string going to send off a request now");
+    if restricted.is_empty() {
+        outputln!("info string error: none of the searchmoves are legal, going to search all legal moves");
+        return legal_moves;
     }
 
-    let mut input_data = vec![
-        InputItem::Item(async_openai::types::responses::Item::Message(
-            async_openai::types::responses::MessageItem::Input(InputMessage {
-                content: vec![InputContent::InputText(InputTextContent {
-                    text: include_str!("../assets/go-simple.md").to_string(),
-                })],
-                role: async_openai::types::responses::InputRole::System,
-                status: None,
-            }),
-        )),
-        InputItem::Item(async_openai::types::responses::Item::Message(
-            async_openai::types::responses::MessageItem::Input(InputMessage {
-                content: vec![InputContent::InputText(InputTextContent { text: fen })],
-                role: async_openai::types::responses::InputRole::User,
-                status: None,
-            }),
-        )),
-        InputItem::Item(async_openai::types::responses::Item::Message(
-            async_openai::types::responses::MessageItem::Input(InputMessage {
-                content: vec![InputContent::InputText(InputTextContent {
-                    text: "Legal moves: ".to_string()
-                        + legal_moves
-                            .iter()
-                            .map(|x| x.to_string())
-                            .collect::<Vec<String>>()
-                            .join(", ")
-                            .as_str(),
-                })],
-                role: async_openai::types::responses::InputRole::User,
-                status: None,
-            }),
-        )),
-    ];
+    restricted
+}
 
-    if !options.additional_instructions.is_empty() {
-        input_data.push(InputItem::Item(
-            async_openai::types::responses::Item::Message(
-                async_openai::types::responses::MessageItem::Input(InputMessage {
-                    content: vec![InputContent::InputText(InputTextContent {
-                        text: options.additional_instructions,
-                    })],
-                    role: async_openai::types::responses::InputRole::System,
-                    status: None,
-                }),
-            ),
-        ));
+fn best(options: &Options, mov: ChessMove, ponder: Option<ChessMove>) {
+    match ponder.filter(|_| options.ponder) {
+        Some(ponder) => {
+            outputln!("bestmove {mov} ponder {ponder}");
+        }
+        None => {
+            outputln!("bestmove {mov}");
+        }
     }
+}
 
-    let req = CreateResponseArgs::default()
-        .model(options.apimodel)
-        .input(InputParam::Items(input_data.clone()))
-        .text(ResponseTextParam {
-            format: async_openai::types::responses::TextResponseFormatConfiguration::JsonSchema(
-                async_openai::types::responses::ResponseFormatJsonSchema {
-                    description: Some("Evaluation output schema".into()),
-                    name: "Evaluation output".into(),
-                    schema: Some(submit_eval_schema),
-                    strict: Some(true),
-                },
-            ),
-            verbosity: Some(async_openai::types::responses::Verbosity::Low),
-        })
-        .build()
-        .unwrap();
+async fn search(
+    options: Options,
+    game: Game,
+    legal_moves: Vec<ChessMove>,
+    previous: Option<String>,
+    latest: Latest,
+    cancellation: CancellationToken,
+) -> Option<Vec<Line>> {
+    let query = Query { options: options.clone(), game, legal_moves, previous, latest, retry: 0, cancellation, sampled: false };
+    if !options.ensemble_file.is_empty() {
+        return ensemble::search(query).await;
+    }
+    consult(query).await
+}
 
-    if options.debug {
-        outputln!(
-            "info string debug going to send this: {}",
-            serde_json::to_string(&req).unwrap()
-        );
+/// Ask the model in the options, with as many samples as there are threads
+async fn consult(query: Query) -> Option<Vec<Line>> {
+    let options = query.options.clone();
+    if options.threads <= 1 {
+        return sample(query).await;
     }
 
-    let client = Client::with_config(
-        OpenAIConfig::new()
-            .with_api_base(options.apibaseurl)
-            .with_api_key(options.apikey),
-    );
-    let res = client.responses().create(req).await;
-    if let Err(res) = res {
-        outputln!("info string error network error while fetching response: {res}");
-        return None;
+    // every thread is a separate query, the samples vote on the move. Until they all answered the leader of the
+    // vote so far is kept as the latest evaluation, so a search that times out or is stopped still plays it
+    let answered = Mutex::new(vec![]);
+    let sampled = Query { sampled: true, ..query.clone() };
+    let samples = join_all((0..options.threads).map(|_| async {
+        let lines = sample(sampled.clone()).await?;
+        let mut answered = answered.lock().unwrap();
+        answered.push(lines.clone());
+        *query.latest.lock().unwrap() = leader(&answered);
+        Some(lines)
+    }))
+    .await;
+    let samples = samples.into_iter().flatten().collect::<Vec<_>>();
+    if samples.len() < options.threads as usize {
+        outputln!("info string error: only {} of {} samples returned a move", samples.len(), options.threads);
     }
 
-    let res = res.unwrap();
+    let lines = vote(samples)?;
+    report(&query, &lines);
+    Some(lines)
+}
 
-    if options.debug {
-        outputln!(
-            "info string debug received response: {}",
-            serde_json::to_string(&res).unwrap()
-        );
-    }
+/// Ask the ai once, trying again until it answers or runs out of tries
+async fn sample(mut query: Query) -> Option<Vec<Line>> {
+    let options = query.options.clone();
+    let backend = Backend::from(options.backend);
 
-    let eval = serde_json::from_str(&res.output_text().unwrap());
-    if let Err(err) = &eval {
-        outputln!(
-            "info string error: could not parse ai's response: {err}, {}",
-            &res.output_text().unwrap()
-        );
-        return None;
-    }
-    let eval: SubmitEval = eval.unwrap();
+    for i in 0..=options.apimaxtries {
+        query.retry = i;
+        let evaluation = backend.evaluate(query.clone());
 
-    let depth = eval.depth.unwrap_or(1.0) as u32;
-    let score = {
-        if let Some(mate) = eval.mate {
-            format!("mate {mate}")
-        } else {
-            let cp = eval.eval;
-            format!("cp {cp}")
-        }
-    };
+        // dropping the evaluation aborts the http request in flight
+        let lines = tokio::select! {
+            _ = query.cancellation.cancelled() => return None,
+            lines = evaluation => lines,
+        };
 
-    if let Some(exp) = eval.reasoning {
-        if exp.len() != 0 {
-            outputln!("info string reasoning: {exp}");
+        if lines.is_some() {
+            return lines;
+        } else {
+            outputln!(
+                "info error: no move found, going to try again ({}/{})",
+                i + 1,
+                options.apimaxtries
+            );
         }
     }
 
-    if eval.ponder.len() == 0 {
-        outputln!("info string error: ai returned no ponder");
-        return None;
-    }
+    None
+}
 
-    let bm = (&eval.ponder).get(0).unwrap();
-    if legal_moves.iter().find(|x| x.to_string() == *bm).is_none() {
-        outputln!("info string error: ai returned an illegal move");
-        return None;
-    }
+async fn timed_search(
+    options: Options,
+    game: Game,
+    legal_moves: Vec<ChessMove>,
+    budget: Option<Duration>,
+    latest: Latest,
+    cancellation: CancellationToken,
+) -> Option<ChessMove> {
+    let search = search(options, game.clone(), legal_moves, None, latest, cancellation);
+    let result = match budget {
+        Some(budget) => match timeout(budget, search).await {
+            Ok(result) => result,
+            Err(_) => {
+                outputln!("info string error: ran out of time budget ({} ms) while waiting for the ai", budget.as_millis());
+                None
+            }
+        },
+        None => search.await,
+    };
 
-    let bm = ChessMove::from_str(bm).unwrap();
-    let pv = eval.ponder.join(" ");
+    result.map(|lines| lines[0].bestmove)
+}
 
-    Some((bm, pv, score, depth))
+/// Keep refining the analysis until `stop`, or until `ponderhit` turns pondering into a normal timed search
+#[allow(clippy::too_many_arguments)]
+async fn analyse(
+    options: Options,
+    game: Game,
+    legal_moves: Vec<ChessMove>,
+    budget: Option<Duration>,
+    ponder: bool,
+    infinite: bool,
+    cancellation: CancellationToken,
+    ponderhit: CancellationToken,
+    latest: Latest,
+    deadline: &mut Option<Instant>,
+) -> Option<ChessMove> {
+    let mut pondering = ponder;
+
+    let mut current = Some(Box::pin(search(options.clone(), game.clone(), legal_moves.clone(), None, latest.clone(), cancellation.clone())));
+
+    loop {
+        let out_of_time = async {
+            match *deadline {
+                Some(deadline) => sleep_until(deadline).await,
+                None => pending().await,
+            }
+        };
+
+        tokio::select! {
+            _ = cancellation.cancelled() => break,
+            _ = ponderhit.cancelled(), if pondering => {
+                pondering = false;
+                if infinite {
+                    continue;
+                }
+                if latest.lock().unwrap().is_some() || current.is_none() {
+                    break;
+                }
+                *deadline = budget.map(|budget| Instant::now() + budget);
+            }
+            _ = out_of_time => {
+                outputln!("info string error: ran out of time budget while waiting for the ai");
+                break;
+            }
+            result = async { current.as_mut().unwrap().await }, if current.is_some() => {
+                current = None;
+                match result {
+                    Some(result) => {
+                        let previous = format!("pv {}, score {} from the side to move's point of view", result[0].pv, result[0].score);
+                        if !pondering && !infinite {
+                            break;
+                        }
+                        current = Some(Box::pin(search(options.clone(), game.clone(), legal_moves.clone(), Some(previous), latest.clone(), cancellation.clone())));
+                    }
+                    None if cancellation.is_cancelled() => break,
+                    None => {
+                        outputln!("info string error: could not refine the analysis, waiting for stop");
+                    }
+                }
+            }
+        }
+    }
+
+    latest_bestmove(&latest)
 }
 
 async fn go(
     args: Vec<String>,
-    board: Board,
-    threads: u16,
-    _cancellation: CancellationToken,
-    stopped_notification: GoStoppedNotification,
+    game: Game,
+    cancellation: CancellationToken,
+    ponderhit: CancellationToken,
+    cache: Cache,
     options: Options,
 ) {
-    let _ = threads;
-
     if options.debug {
         outputln!("info string go command worker entered with these options: {options:?}");
     }
+    if options.uci_limit_strength {
+        outputln!("info string playing at about {} Elo", options.uci_elo);
+    }
+
+    let searchmoves = process_keyword(&args, "searchmoves".into());
 
     #[allow(unused)]
-    let (
-        searchmoves,
-        ponder,
-        wtime,
-        btime,
-        winc,
-        binc,
-        movestogo,
-        depth,
-        nodes,
-        mate,
-        movetime,
-        infinite,
-    ) = (
-        process_keyword(&args, "searchmoves".into()),
-        process_keyword(&args, "ponder".into()),
-        process_keyword(&args, "wtime".into()),
-        process_keyword(&args, "btime".into()),
-        process_keyword(&args, "winc".into()),
-        process_keyword(&args, "binc".into()),
-        process_keyword(&args, "movestogo".into()),
+    let (depth, nodes, mate) = (
         process_keyword(&args, "depth".into()),
         process_keyword(&args, "nodes".into()),
         process_keyword(&args, "mate".into()),
-        process_keyword(&args, "movetime".into()),
-        process_keyword(&args, "infinite".into()),
     );
+    let (ponder, infinite) = (has_keyword(&args, "ponder"), has_keyword(&args, "infinite"));
+
+    let millis = |keyword: &str| process_keyword(&args, keyword.into()).and_then(|x| x.parse::<u64>().ok());
+    let time_control = TimeControl {
+        wtime: millis("wtime"),
+        btime: millis("btime"),
+        winc: millis("winc"),
+        binc: millis("binc"),
+        movestogo: millis("movestogo"),
+        movetime: millis("movetime"),
+    };
 
-    let legal_moves = legal_moves(board);
-    if legal_moves.len() == 0 {
+    let board = game.board();
+    let legal_moves = game.legal_moves();
+    if legal_moves.is_empty() {
         outputln!(
             "info string error: refusing to evaluate on a board with no legal moves, considering the position draw by stalemate"
         );
         outputln!("info depth 1 score cp 0");
+        outputln!("bestmove 0000");
         return;
     }
 
-    if legal_moves.len() == 1 {
+    let legal_moves = match searchmoves {
+        Some(searchmoves) => restrict_to_searchmoves(legal_moves, &searchmoves),
+        None => legal_moves,
+    };
+
+    if legal_moves.len() == 1 && !ponder && !infinite {
         // not going to evaluate a forced position
-        return best(legal_moves.get(0).unwrap().clone());
+        return best(&options, legal_moves[0], None);
     }
 
-    for i in 0..=options.apimaxtries {
-        if let Some((bm, pv, score, depth)) =
-            try_get_bestmove(options.clone(), board, legal_moves.clone()).await
-        {
-            outputln!("info depth {depth} score {score} pv {pv}");
-
-            stopped_notification.lock().await.notify_waiters();
+    let budget = time_control.budget(board.side_to_move(), options.move_overhead as u64);
+    if options.debug {
+        outputln!("info string debug time budget for this move: {budget:?}");
+    }
 
-            outputln!("bestmove {bm}");
-            return;
+    let latest = Latest::default();
+    let mut deadline = None;
+    let bm = if ponder || infinite {
+        // the gui expects a bestmove only after stop or ponderhit, so we keep thinking until then
+        analyse(options.clone(), game.clone(), legal_moves.clone(), budget, ponder, infinite, cancellation, ponderhit, latest.clone(), &mut deadline).await
+    } else {
+        deadline = budget.map(|budget| Instant::now() + budget);
+        tokio::select! {
+            // a tool search may have submitted something before it was stopped
+            _ = cancellation.cancelled() => {
+                if let Some(lines) = latest.lock().unwrap().as_deref().filter(|_| options.threads > 1) {
+                    outputln!("info string stopped, going with the vote of the samples that answered so far");
+                    print_lines(&options, lines);
+                }
+                latest_bestmove(&latest)
+            }
+            bm = timed_search(options.clone(), game.clone(), legal_moves.clone(), budget, latest.clone(), cancellation.clone()) => bm,
+        }
+    };
+    let bm = bm.or_else(|| {
+        let bm = latest_bestmove(&latest)?;
+        if options.threads > 1 {
+            outputln!("info string going with the vote of the samples that answered so far");
+            print_lines(&options, latest.lock().unwrap().as_deref()?);
         } else {
-            outputln!(
-                "info error: no move found, going to try again ({}/{})",
-                i + 1,
-                options.apimaxtries
-            );
+            outputln!("info string going with the latest evaluation the ai has submitted");
         }
+        Some(bm)
+    });
+    let bm = bm.or_else(|| {
+        let lines = cache.lock().unwrap().get(&cache_key(&board)).cloned()?;
+        let line = lines.into_iter().find(|x| legal_moves.contains(&x.bestmove))?;
+        outputln!("info string going with an earlier evaluation of this position, pv {}", line.pv);
+        *latest.lock().unwrap() = Some(vec![line.clone()]);
+        Some(line.bestmove)
+    });
+    if let Some(lines) = latest.lock().unwrap().clone() {
+        cache.lock().unwrap().insert(cache_key(&board), lines);
     }
+    let bm = bm.map(|bm| match (options.uci_limit_strength, latest.lock().unwrap().as_deref()) {
+        (true, Some(lines)) => weaken(options.uci_elo, lines, &legal_moves, bm),
+        _ => bm,
+    });
+    let bm = bm.map(|bm| match (options.draw_moves, latest.lock().unwrap().as_deref()) {
+        (DrawMoves::Override, Some(lines)) => override_draws(&game, lines, &legal_moves, bm),
+        _ => bm,
+    });
 
-    let mut legal_moves = legal_moves;
-    legal_moves.shuffle(&mut rng());
+    if let Some(bm) = bm {
+        // the ai's expected reply, if the move we play is one of its lines
+        let ponder = latest
+            .lock()
+            .unwrap()
+            .as_deref()
+            .and_then(|lines| lines.iter().find(|x| x.bestmove == bm)?.ponder);
+        return best(&options, bm, ponder);
+    }
 
-    outputln!("info error: no bestmove was found in 3 tries, going to pick a random move");
-    outputln!("bestmove {}", legal_moves.first().unwrap());
+    // the ai gave nothing: it failed too many times, ran out of time or was stopped before answering
+    let cap = match deadline {
+        Some(deadline) => deadline.saturating_duration_since(Instant::now()).clamp(fallback::MIN_TIME, fallback::TIME_CAP),
+        None => fallback::TIME_CAP,
+    };
+    let fallback = tokio::task::spawn_blocking(move || fallback::search(&game, &legal_moves, cap)).await.unwrap();
+    let pv = fallback.pv.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(" ");
+    outputln!(
+        "info string error: no bestmove was found, this move comes from the fallback material search and not from the ai"
+    );
+    outputln!("info depth {} score {} pv {pv}", fallback.depth, fallback.score);
+    best(&options, fallback.pv[0], fallback.pv.get(1).copied());
 }
 
 impl ICommand for GoCommand {
@@ -384,20 +419,232 @@ impl ICommand for GoCommand {
             *guard = Some(token);
         }
 
-        let board = state.board;
-        let threads = state.options.threads;
+        let ponder_hit = state.ponder_hit.clone();
+        let ponderhit_token = CancellationToken::new();
+        *ponder_hit.lock().await = Some(ponderhit_token.clone());
+
+        let game = state.game.clone();
         let go_stop_notify = state.go_stopped_notification.clone();
+        let cache = state.cache.clone();
 
         tokio::spawn(async move {
-            tokio::select! {
-                _ = task_token.cancelled() => {}
-                _ = go(args, board, threads, task_token.clone(), go_stop_notify, options) => {}
-            }
+            go(args, game, task_token, ponderhit_token, cache, options).await;
 
+            *ponder_hit.lock().await = None;
             let mut guard = cancel_go.lock().await;
             *guard = None;
+            drop(guard);
+
+            // go prints a bestmove however it ends, this is its only exit
+            go_stop_notify.lock().await.notify_waiters();
         });
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::{sleep, timeout};
+
+    use crate::backend::record::Record;
+    use crate::command::Command;
+    use crate::state::State;
+    use crate::utils::tests::take_output;
+
+    async fn send(state: &mut State, lines: &[&str]) {
+        for line in lines {
+            Command::process_line(line.to_string(), state).await;
+        }
+    }
+
+    /// Everything the engine printed up to and including its bestmove
+    async fn until_bestmove() -> Vec<String> {
+        let mut output = vec![];
+        let waiting = async {
+            while !output.iter().any(|x: &String| x.starts_with("bestmove")) {
+                sleep(Duration::from_millis(10)).await;
+                output.extend(take_output());
+            }
+        };
+        timeout(Duration::from_secs(10), waiting).await.expect("the engine never printed a bestmove");
+        output
+    }
+
+    fn count(output: &[String], text: &str) -> usize {
+        output.iter().filter(|x| x.contains(text)).count()
+    }
+
+    fn assert_legal_bestmove(state: &State, output: &[String]) {
+        let bestmove = output.iter().find(|x| x.starts_with("bestmove")).unwrap();
+        let mov = bestmove.split_whitespace().nth(1).unwrap();
+        assert!(state.game.legal_moves().iter().any(|x| x.to_string() == mov), "{bestmove} is not legal");
+    }
+
+    #[tokio::test]
+    async fn plays_the_mocked_answer() {
+        let mut state = State::default();
+        send(&mut state, &[
+            "setoption name Backend value Mock",
+            r#"setoption name MockResponse value {"ponder":["e2e4","e7e5"],"eval":0.3}"#,
+            "position startpos",
+            "go movetime 5000",
+        ])
+        .await;
+
+        let output = until_bestmove().await;
+        assert!(output.contains(&"info multipv 1 depth 1 score cp 30 pv e2e4 e7e5".to_string()));
+        assert_eq!(output.last().unwrap(), "bestmove e2e4 ponder e7e5");
+    }
+
+    #[tokio::test]
+    async fn parallel_samples_only_report_their_vote() {
+        let mut state = State::default();
+        send(&mut state, &[
+            "setoption name Backend value Mock",
+            r#"setoption name MockResponse value {"ponder":["e2e4","e7e5"],"eval":0.3}"#,
+            "setoption name Threads value 3",
+            "position startpos",
+            "go movetime 5000",
+        ])
+        .await;
+
+        let output = until_bestmove().await;
+        assert_eq!(count(&output, "info multipv 1 "), 1);
+        assert_eq!(count(&output, "e2e4 won the vote of 3 samples"), 1);
+        assert_eq!(output.last().unwrap(), "bestmove e2e4 ponder e7e5");
+    }
+
+    #[tokio::test]
+    async fn recorded_games_are_replayed() {
+        let file = std::env::temp_dir().join(format!("record-{}.jsonl", std::process::id()));
+        let file = file.to_str().unwrap();
+        let mut state = State::default();
+        send(&mut state, &[
+            "setoption name Backend value Mock",
+            &format!("setoption name RecordFile value {file}"),
+            "position startpos",
+            r#"setoption name MockResponse value {"ponder":["d2d4","d7d5"],"eval":0.1}"#,
+            "go movetime 5000",
+        ])
+        .await;
+        until_bestmove().await;
+        send(&mut state, &[
+            r#"setoption name MockResponse value {"ponder":["e2e4","e7e5"],"eval":0.3}"#,
+            "go movetime 5000",
+        ])
+        .await;
+        until_bestmove().await;
+
+        // a tool turn without a submission is no evaluation, the last one of the position is replayed
+        let records = std::fs::read_to_string(file).unwrap();
+        let mut tool_turn = serde_json::from_str::<Record>(records.lines().next().unwrap()).unwrap();
+        (tool_turn.parsed, tool_turn.tool_turn) = (None, true);
+        std::fs::write(file, serde_json::to_string(&tool_turn).unwrap() + "\n" + &records).unwrap();
+
+        send(&mut state, &[
+            "setoption name Backend value Replay",
+            "setoption name RecordFile value ",
+            &format!("setoption name ReplayFile value {file}"),
+            "setoption name APIMaxTries value 0",
+            "go movetime 5000",
+        ])
+        .await;
+        let output = until_bestmove().await;
+        std::fs::remove_file(file).unwrap();
+        assert!(output.contains(&"info multipv 1 depth 1 score cp 30 pv e2e4 e7e5".to_string()));
+        assert_eq!(output.last().unwrap(), "bestmove e2e4 ponder e7e5");
+    }
+
+    #[tokio::test]
+    async fn malformed_answers_are_retried_then_fall_back() {
+        let mut state = State::default();
+        send(&mut state, &[
+            "setoption name Backend value Mock",
+            "setoption name MockResponse value not json",
+            "setoption name APIMaxTries value 2",
+            "position startpos moves e2e4",
+            "go movetime 5000",
+        ])
+        .await;
+
+        let output = until_bestmove().await;
+        assert_eq!(count(&output, "could not parse ai's response"), 3);
+        assert_eq!(count(&output, "fallback material search"), 1);
+        assert_legal_bestmove(&state, &output);
+    }
+
+    #[tokio::test]
+    async fn slow_answers_time_out_then_fall_back() {
+        let mut state = State::default();
+        send(&mut state, &[
+            "setoption name Backend value Mock",
+            "setoption name MockLatencyMs value 1000",
+            "setoption name APITimeoutMs value 20",
+            "setoption name APIMaxTries value 1",
+            "position startpos",
+            "go wtime 60000 btime 60000",
+        ])
+        .await;
+
+        let output = until_bestmove().await;
+        assert_eq!(count(&output, "the api didn't answer within 20 ms"), 2);
+        assert_eq!(count(&output, "fallback material search"), 1);
+        assert_legal_bestmove(&state, &output);
+    }
+
+    #[tokio::test]
+    async fn stop_answers_with_a_bestmove_while_waiting_for_the_ai() {
+        let mut state = State::default();
+        send(&mut state, &[
+            "setoption name Backend value Mock",
+            "setoption name MockLatencyMs value 10000",
+            "position startpos",
+            "go infinite",
+        ])
+        .await;
+        sleep(Duration::from_millis(50)).await;
+        send(&mut state, &["stop"]).await;
+
+        let output = until_bestmove().await;
+        assert_eq!(count(&output, "fallback material search"), 1);
+        assert_legal_bestmove(&state, &output);
+    }
+
+    #[tokio::test]
+    async fn stop_answers_with_the_latest_analysis() {
+        let mut state = State::default();
+        send(&mut state, &[
+            "setoption name Backend value Mock",
+            "setoption name MockLatencyMs value 10",
+            r#"setoption name MockResponse value {"ponder":["d2d4","d7d5"],"eval":0.2}"#,
+            "position startpos",
+            "go infinite",
+        ])
+        .await;
+        sleep(Duration::from_millis(100)).await;
+        send(&mut state, &["stop"]).await;
+
+        let output = until_bestmove().await;
+        assert_eq!(output.last().unwrap(), "bestmove d2d4 ponder d7d5");
+    }
+
+    #[tokio::test]
+    async fn isready_is_answered_while_searching() {
+        let mut state = State::default();
+        send(&mut state, &[
+            "setoption name Backend value Mock",
+            "setoption name MockLatencyMs value 10000",
+            "position startpos",
+            "go infinite",
+            "isready",
+        ])
+        .await;
+        assert!(take_output().contains(&"readyok".to_string()));
+
+        send(&mut state, &["stop"]).await;
+        until_bestmove().await;
+    }
+}
@@ -0,0 +1,44 @@
+use chess::ChessMove;
+
+use crate::backend::Line;
+use crate::outputln;
+use crate::state::game::Game;
+
+/// How many pawns one side has to be ahead by to not want a draw
+const DRAW_MARGIN: f32 = 1.0;
+
+/// Play around the draw rules with the ai's own evaluation: don't draw a won game and take the draw in a lost one
+pub fn override_draws(game: &Game, lines: &[Line], legal_moves: &[ChessMove], bm: ChessMove) -> ChessMove {
+    let Some(best) = lines.iter().find(|x| x.bestmove == bm) else {
+        return bm;
+    };
+    let advantage = best.score.pawns();
+
+    if let Some(rule) = game.draw_after(bm) {
+        if advantage <= DRAW_MARGIN {
+            return bm;
+        }
+        return match lines.iter().find(|x| game.draw_after(x.bestmove).is_none()) {
+            Some(line) => {
+                outputln!(
+                    "info string {bm} draws by the {rule} while the ai thinks we are {advantage} pawns ahead, playing {} instead",
+                    line.bestmove
+                );
+                line.bestmove
+            }
+            None => {
+                outputln!("info string {bm} draws by the {rule} while the ai thinks we are ahead, but it has no other line");
+                bm
+            }
+        };
+    }
+
+    if advantage < -DRAW_MARGIN
+        && let Some((mov, rule)) = legal_moves.iter().find_map(|x| Some((*x, game.draw_after(*x)?)))
+    {
+        outputln!("info string the ai thinks we are {} pawns behind, taking the draw by the {rule} with {mov}", -advantage);
+        return mov;
+    }
+
+    bm
+}
@@ -0,0 +1,121 @@
+use futures::future::join_all;
+use serde::Deserialize;
+use tokio::fs;
+
+use crate::backend::{Line, Query, report};
+use crate::command::go::consult;
+use crate::command::go::vote::vote;
+use crate::outputln;
+use crate::state::options::{BackendKind, EnsemblePolicy};
+
+/// A model of the ensemble, as listed in the `EnsembleFile`. Unset fields fall back to the options
+#[derive(Debug, Clone, Deserialize)]
+struct Member {
+    name: String,
+    model: Option<String>,
+    base_url: Option<String>,
+    api_key: Option<String>,
+    backend: Option<String>,
+    mock_response: Option<String>,
+}
+
+impl Member {
+    /// The query with the options of this member
+    fn query(&self, query: &Query) -> Result<Query, String> {
+        let mut query = query.clone();
+        let options = &mut query.options;
+
+        if let Some(model) = &self.model {
+            options.apimodel = model.clone();
+        }
+        if let Some(base_url) = &self.base_url {
+            options.apibaseurl = base_url.clone();
+        }
+        if let Some(api_key) = &self.api_key {
+            options.apikey = api_key.clone();
+        }
+        if let Some(backend) = &self.backend {
+            options.backend = backend.parse::<BackendKind>()?;
+        }
+        if let Some(mock_response) = &self.mock_response {
+            options.mock_response = mock_response.clone();
+        }
+
+        Ok(query)
+    }
+}
+
+/// Read the members, which are listed from the weakest to the strongest
+async fn members(file: &str) -> Result<Vec<Member>, String> {
+    let members = fs::read_to_string(file).await.map_err(|err| format!("couldn't read the ensemble file {file}: {err}"))?;
+    let members = serde_json::from_str::<Vec<Member>>(&members)
+        .map_err(|err| format!("couldn't parse the ensemble file {file}: {err}"))?;
+
+    if members.is_empty() {
+        return Err(format!("the ensemble file {file} has no models"));
+    }
+    Ok(members)
+}
+
+/// Ask all of the members at once, leaving out the ones that failed
+async fn ask(members: &[Member], query: &Query) -> Vec<(String, Vec<Line>)> {
+    let answers = join_all(members.iter().map(|member| async move {
+        let query = match member.query(query) {
+            Ok(query) => query,
+            Err(err) => {
+                outputln!("info string error: ensemble model {}: {err}", member.name);
+                return None;
+            }
+        };
+        Some((member.name.clone(), consult(query).await?))
+    }))
+    .await;
+
+    let answers = answers.into_iter().flatten().collect::<Vec<_>>();
+    for (name, lines) in &answers {
+        outputln!("info string ensemble model {name} plays {} with score {}", lines[0].bestmove, lines[0].score);
+    }
+    answers
+}
+
+/// Consult every model of the `EnsembleFile` and let the `EnsemblePolicy` decide between their answers
+pub async fn search(query: Query) -> Option<Vec<Line>> {
+    let options = &query.options;
+    let members = match members(&options.ensemble_file).await {
+        Ok(members) => members,
+        Err(err) => {
+            outputln!("info string error: {err}");
+            return None;
+        }
+    };
+
+    let lines = match options.ensemble_policy {
+        EnsemblePolicy::Vote => {
+            let answers = ask(&members, &query).await;
+            vote(answers.into_iter().map(|x| x.1).collect())?
+        }
+        EnsemblePolicy::Strongest => {
+            let mut answers = ask(&members, &query).await;
+            let (name, lines) = answers.pop()?;
+            outputln!("info string going with {name}, the strongest model that answered");
+            lines
+        }
+        EnsemblePolicy::Escalate => {
+            let (strongest, weaker) = members.split_last().unwrap();
+            let answers = ask(weaker, &query).await;
+
+            let agreed = answers.first().is_some_and(|(_, first)| answers.iter().all(|(_, x)| x[0].bestmove == first[0].bestmove));
+            if agreed {
+                outputln!("info string the weaker models agree on {}", answers[0].1[0].bestmove);
+                vote(answers.into_iter().map(|x| x.1).collect())?
+            } else {
+                outputln!("info string the weaker models don't agree, escalating to {}", strongest.name);
+                let (_, lines) = ask(std::slice::from_ref(strongest), &query).await.pop()?;
+                lines
+            }
+        }
+    };
+
+    report(&query, &lines);
+    Some(lines)
+}
@@ -0,0 +1,212 @@
+use std::time::{Duration, Instant};
+
+use chess::{Board, BoardStatus, ChessMove, EMPTY, MoveGen};
+
+use crate::backend::score::Score;
+use crate::backend::tools::{material, piece_value};
+use crate::state::game::Game;
+
+/// How deep the fallback search goes at most, in halfmoves
+const MAX_DEPTH: u8 = 4;
+
+/// The longest the fallback search may take, however much time there is left
+pub const TIME_CAP: Duration = Duration::from_millis(250);
+
+/// The shortest the fallback search gets, even when the move's time budget has already run out
+pub const MIN_TIME: Duration = Duration::from_millis(10);
+
+/// Score of being mated right now, mates further away score a bit less
+const MATE: i32 = 100_000;
+
+/// Longer than any line the search can look at, captures included, so scores this close to `MATE` are mates
+const MAX_PLIES: i32 = 256;
+
+/// Checked every this many nodes, so looking at the clock doesn't slow down the search
+const NODES_PER_CLOCK_CHECK: u64 = 1024;
+
+/// The result of the fallback search
+pub struct Fallback {
+    pub pv: Vec<ChessMove>,
+    pub score: Score,
+    pub depth: u8,
+}
+
+struct Search {
+    deadline: Instant,
+    nodes: u64,
+}
+
+/// Material from the side to move's point of view, in centipawns
+fn evaluate(board: &Board) -> i32 {
+    let side = board.side_to_move();
+    (material(board, side) as i32 - material(board, !side) as i32) * 100
+}
+
+/// Captures of the most valuable pieces by the least valuable ones first, they cut the search off the most
+fn ordered(board: &Board, mut moves: Vec<ChessMove>) -> Vec<ChessMove> {
+    moves.sort_by_key(|x| {
+        let Some(victim) = board.piece_on(x.get_dest()).filter(|_| board.color_on(x.get_dest()) != Some(board.side_to_move())) else {
+            return 0;
+        };
+        let attacker = board.piece_on(x.get_source()).map(piece_value).unwrap_or(0) as i32;
+        -(piece_value(victim) as i32 * 10 - attacker)
+    });
+    moves
+}
+
+impl Search {
+    /// Negamax with alpha-beta pruning. Returns `None` once out of time
+    fn negamax(&mut self, board: &Board, depth: u8, ply: i32, mut alpha: i32, beta: i32) -> Option<(i32, Vec<ChessMove>)> {
+        self.nodes += 1;
+        if self.nodes.is_multiple_of(NODES_PER_CLOCK_CHECK) && Instant::now() > self.deadline {
+            return None;
+        }
+
+        match board.status() {
+            BoardStatus::Checkmate => return Some((-MATE + ply, vec![])),
+            BoardStatus::Stalemate => return Some((0, vec![])),
+            BoardStatus::Ongoing => {}
+        }
+        if depth == 0 {
+            return self.quiesce(board, ply, alpha, beta);
+        }
+
+        let mut best = vec![];
+        for mov in ordered(board, MoveGen::new_legal(board).collect()) {
+            let (score, pv) = self.negamax(&board.make_move_new(mov), depth - 1, ply + 1, -beta, -alpha)?;
+            if -score > alpha {
+                alpha = -score;
+                best = [vec![mov], pv].concat();
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+        Some((alpha, best))
+    }
+
+    /// Only look at captures until the position is quiet, so the last capture of the search doesn't go unanswered.
+    /// Every move is looked at when in check, there is no standing pat then
+    fn quiesce(&mut self, board: &Board, ply: i32, mut alpha: i32, beta: i32) -> Option<(i32, Vec<ChessMove>)> {
+        let mut moves = MoveGen::new_legal(board);
+        if *board.checkers() == EMPTY {
+            let stand_pat = evaluate(board);
+            if stand_pat >= beta {
+                return Some((stand_pat, vec![]));
+            }
+            alpha = alpha.max(stand_pat);
+            moves.set_iterator_mask(*board.color_combined(!board.side_to_move()));
+        }
+
+        let mut best = vec![];
+        for mov in ordered(board, moves.collect()) {
+            let (score, pv) = self.negamax(&board.make_move_new(mov), 0, ply + 1, -beta, -alpha)?;
+            if -score > alpha {
+                alpha = -score;
+                best = [vec![mov], pv].concat();
+                if alpha >= beta {
+                    break;
+                }
+            }
+        }
+        Some((alpha, best))
+    }
+
+    /// The root moves come with the boards after them, Chess960 castling is only played by `Game`
+    fn root(&mut self, moves: &[(ChessMove, Board)], depth: u8) -> Option<(i32, Vec<ChessMove>)> {
+        let mut best = (-MATE - 1, vec![]);
+        for (mov, board) in moves {
+            let (score, pv) = self.negamax(board, depth - 1, 1, -MATE - 1, -best.0)?;
+            if -score > best.0 {
+                best = (-score, [vec![*mov], pv].concat());
+            }
+        }
+        Some(best)
+    }
+}
+
+fn score(cp: i32) -> Score {
+    let plies = MATE - cp.abs();
+    if plies > MAX_PLIES {
+        return Score::Cp(cp);
+    }
+    let moves = (plies + 1) / 2;
+    Score::Mate(if cp > 0 { moves } else { -moves })
+}
+
+/// Search the legal moves for material a few halfmoves deep, going deeper until the time cap
+pub fn search(game: &Game, legal_moves: &[ChessMove], cap: Duration) -> Fallback {
+    let mut search = Search { deadline: Instant::now() + cap, nodes: 0 };
+    let board = game.board();
+    let mut moves = ordered(&board, legal_moves.to_vec()).into_iter().map(|x| (x, game.board_after(x))).collect::<Vec<_>>();
+    let mut fallback = Fallback { pv: vec![moves[0].0], score: score(evaluate(&board)), depth: 0 };
+
+    for depth in 1..=MAX_DEPTH {
+        let Some((cp, pv)) = search.root(&moves, depth) else {
+            break;
+        };
+
+        // the best move of this depth is searched first on the next one
+        let best = moves.iter().position(|x| x.0 == pv[0]).unwrap();
+        let best = moves.remove(best);
+        moves.insert(0, best);
+
+        fallback = Fallback { pv, score: score(cp), depth };
+    }
+
+    fallback
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn search_fen(fen: &str) -> Fallback {
+        let game = Game::new(Board::from_str(fen).unwrap(), Some(fen.to_string()), None);
+        search(&game, &game.legal_moves(), Duration::from_secs(5))
+    }
+
+    fn pv(fallback: &Fallback) -> String {
+        fallback.pv.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(" ")
+    }
+
+    #[test]
+    fn finds_a_mate_in_one() {
+        let fallback = search_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1");
+        assert_eq!(pv(&fallback), "a1a8");
+        assert_eq!(fallback.score, Score::Mate(1));
+    }
+
+    #[test]
+    fn takes_a_free_piece() {
+        let fallback = search_fen("4k3/8/8/3q4/8/8/8/3RK3 w - - 0 1");
+        assert_eq!(fallback.pv[0].to_string(), "d1d5");
+        assert_eq!(fallback.score, Score::Cp(500));
+    }
+
+    #[test]
+    fn does_not_take_a_defended_pawn_with_the_queen() {
+        let fallback = search_fen("4k3/2p5/3p4/8/8/8/8/3QK3 w - - 0 1");
+        assert_ne!(fallback.pv[0].to_string(), "d1d6");
+        assert_eq!(fallback.score, Score::Cp(700));
+    }
+
+    #[test]
+    fn answers_the_last_capture_of_the_line() {
+        // the queen can take on f2 at the end of a 4 ply line, but the king takes it back
+        let fallback = search_fen("rnbqkbnr/pppppppp/8/8/8/5N2/PPPPPPPP/RNBQKB1R b KQkq - 1 1");
+        assert!(matches!(fallback.score, Score::Cp(cp) if cp.abs() < 100), "{}: {}", pv(&fallback), fallback.score);
+    }
+
+    #[test]
+    fn stops_at_the_time_cap_with_a_legal_move() {
+        let fen = "r1bq1rk1/pp2bppp/2n1pn2/2pp4/2PP4/2N1PN2/PP2BPPP/R1BQ1RK1 w - - 0 8";
+        let game = Game::new(Board::from_str(fen).unwrap(), Some(fen.to_string()), None);
+        let started = Instant::now();
+        let fallback = search(&game, &game.legal_moves(), Duration::from_millis(1));
+        assert!(started.elapsed() < Duration::from_millis(100));
+        assert!(game.is_legal(fallback.pv[0]));
+    }
+}
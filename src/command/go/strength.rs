@@ -0,0 +1,55 @@
+use chess::ChessMove;
+use rand::seq::IndexedRandom;
+use rand::{RngExt, rng};
+
+use crate::backend::Line;
+use crate::outputln;
+
+/// The Elo at which the best line is always played
+const MAX_ELO: u16 = 2800;
+
+/// The lowest Elo `UCI_Elo` goes down to
+const MIN_ELO: u16 = 800;
+
+/// Chance of not playing the best line at the lowest Elo
+const MAX_DEVIATION: f64 = 0.6;
+
+/// Chance of not playing the best line at the given Elo
+fn deviation(elo: u16) -> f64 {
+    let elo = elo.clamp(MIN_ELO, MAX_ELO);
+    (MAX_ELO - elo) as f64 / (MAX_ELO - MIN_ELO) as f64 * MAX_DEVIATION
+}
+
+/// Sometimes play one of the ai's other lines or a random move instead of the best one, less often the higher the Elo
+pub fn weaken(elo: u16, lines: &[Line], legal_moves: &[ChessMove], bm: ChessMove) -> ChessMove {
+    let mut rng = rng();
+    let deviation = deviation(elo);
+    if !rng.random_bool(deviation) {
+        return bm;
+    }
+
+    // weaker players sometimes play a move nobody would have considered
+    if rng.random_bool(deviation / 3.0) {
+        let mov = *legal_moves.choose(&mut rng).unwrap();
+        outputln!("info string limiting strength to {elo} Elo, playing the random move {mov} instead of {bm}");
+        return mov;
+    }
+
+    let Some(best) = lines.iter().find(|x| x.bestmove == bm) else {
+        return bm;
+    };
+    let best = best.score.pawns();
+
+    // lines the ai thinks are almost as good as the best one are a lot more likely to be picked
+    let alternatives = lines.iter().filter(|x| x.bestmove != bm).collect::<Vec<_>>();
+    let Ok(line) = alternatives.choose_weighted(&mut rng, |x| (-(best - x.score.pawns()).max(0.0)).exp()) else {
+        return bm;
+    };
+
+    outputln!(
+        "info string limiting strength to {elo} Elo, playing {} from the ai's worse line {} instead of {bm}",
+        line.bestmove,
+        line.pv
+    );
+    line.bestmove
+}
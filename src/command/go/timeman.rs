@@ -0,0 +1,96 @@
+use std::time::Duration;
+
+use chess::Color;
+
+/// How many moves we assume are left in the game when the GUI doesn't send `movestogo`
+const DEFAULT_MOVES_TO_GO: u64 = 30;
+
+/// The smallest budget we are going to hand out, even if the clock is almost empty
+const MIN_BUDGET_MS: u64 = 10;
+
+/// Clock information received with the `go` command. All values are in milliseconds
+#[derive(Debug, Clone, Default)]
+pub struct TimeControl {
+    pub wtime: Option<u64>,
+    pub btime: Option<u64>,
+    pub winc: Option<u64>,
+    pub binc: Option<u64>,
+    pub movestogo: Option<u64>,
+    pub movetime: Option<u64>,
+}
+
+impl TimeControl {
+    /// Figure out how long we are allowed to think for this move.
+    ///
+    /// Returns `None` if the search isn't limited by time (for example `go depth 3` or a bare `go`).
+    /// `overhead` is subtracted from the budget to account for the network and the GUI lag.
+    pub fn budget(&self, side: Color, overhead: u64) -> Option<Duration> {
+        if let Some(movetime) = self.movetime {
+            return Some(Self::with_overhead(movetime, overhead));
+        }
+
+        let (time, inc) = match side {
+            Color::White => (self.wtime?, self.winc.unwrap_or(0)),
+            Color::Black => (self.btime?, self.binc.unwrap_or(0)),
+        };
+
+        let moves_to_go = self.movestogo.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+        let budget = (time / moves_to_go + inc * 3 / 4).min(time);
+
+        Some(Self::with_overhead(budget, overhead))
+    }
+
+    fn with_overhead(budget: u64, overhead: u64) -> Duration {
+        Duration::from_millis(budget.saturating_sub(overhead).max(MIN_BUDGET_MS))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Option<Duration> {
+        Some(Duration::from_millis(millis))
+    }
+
+    #[test]
+    fn movetime_is_used_as_is_minus_the_overhead() {
+        let time_control = TimeControl { movetime: Some(2000), wtime: Some(100), ..Default::default() };
+        assert_eq!(time_control.budget(Color::White, 100), ms(1900));
+    }
+
+    #[test]
+    fn the_clock_is_split_over_the_moves_to_go() {
+        let time_control = TimeControl { wtime: Some(60000), btime: Some(30000), movestogo: Some(10), ..Default::default() };
+        assert_eq!(time_control.budget(Color::White, 0), ms(6000));
+        assert_eq!(time_control.budget(Color::Black, 0), ms(3000));
+
+        let time_control = TimeControl { wtime: Some(60000), ..Default::default() };
+        assert_eq!(time_control.budget(Color::White, 0), ms(60000 / DEFAULT_MOVES_TO_GO));
+    }
+
+    #[test]
+    fn most_of_the_increment_is_spent() {
+        let time_control = TimeControl { btime: Some(30000), binc: Some(2000), movestogo: Some(30), ..Default::default() };
+        assert_eq!(time_control.budget(Color::Black, 0), ms(1000 + 1500));
+    }
+
+    #[test]
+    fn the_budget_never_exceeds_the_clock() {
+        let time_control = TimeControl { wtime: Some(500), winc: Some(10000), ..Default::default() };
+        assert_eq!(time_control.budget(Color::White, 0), ms(500));
+    }
+
+    #[test]
+    fn an_almost_empty_clock_still_gets_the_minimum() {
+        let time_control = TimeControl { wtime: Some(3000), ..Default::default() };
+        assert_eq!(time_control.budget(Color::White, 1000), ms(MIN_BUDGET_MS));
+    }
+
+    #[test]
+    fn no_budget_without_a_clock() {
+        let time_control = TimeControl { btime: Some(1000), ..Default::default() };
+        assert_eq!(time_control.budget(Color::White, 0), None);
+        assert_eq!(TimeControl::default().budget(Color::Black, 0), None);
+    }
+}
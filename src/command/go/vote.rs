@@ -0,0 +1,105 @@
+use chess::ChessMove;
+
+use crate::backend::Line;
+use crate::backend::score::Score;
+use crate::outputln;
+
+/// How much a vote counts, the more the voter thinks its move wins the more it counts
+fn weight(line: &Line) -> f32 {
+    1.0 / (1.0 + (-line.score.pawns()).exp())
+}
+
+/// The median mate if every voter sees one, otherwise the average of the centipawn scores
+fn average(voters: &[&Line]) -> Score {
+    let (mut mates, mut cps) = (vec![], vec![]);
+    for voter in voters {
+        match voter.score {
+            Score::Cp(cp) => cps.push(cp),
+            Score::Mate(moves) => mates.push(moves),
+        }
+    }
+
+    if cps.is_empty() {
+        mates.sort();
+        return Score::Mate(mates[mates.len() / 2]);
+    }
+
+    Score::Cp(cps.iter().sum::<i32>() / cps.len() as i32)
+}
+
+type Tally<'a> = Vec<(ChessMove, Vec<&'a Line>, f32)>;
+
+/// Count the weighted votes for the first move of every sample, the best supported move first
+fn tally(samples: &[Vec<Line>]) -> Option<Tally<'_>> {
+    let mut tally: Tally = vec![];
+    for sample in samples {
+        let line = sample.first()?;
+        match tally.iter_mut().find(|x| x.0 == line.bestmove) {
+            Some(entry) => {
+                entry.1.push(line);
+                entry.2 += weight(line);
+            }
+            None => tally.push((line.bestmove, vec![line], weight(line))),
+        }
+    }
+    tally.sort_by(|a, b| b.2.total_cmp(&a.2).then(b.1.len().cmp(&a.1.len())));
+    Some(tally)
+}
+
+/// The lines of one of the winner's voters, with the averaged score
+fn winner(samples: &[Vec<Line>], tally: &Tally) -> Option<Vec<Line>> {
+    let (winner, voters, _) = tally.first()?;
+    let lines = samples.iter().find(|x| x[0].bestmove == *winner)?;
+    let mut lines = lines.clone();
+    lines[0].score = average(voters);
+    Some(lines)
+}
+
+/// The lines that are ahead in the vote of the samples so far, without reporting anything
+pub fn leader(samples: &[Vec<Line>]) -> Option<Vec<Line>> {
+    winner(samples, &tally(samples)?)
+}
+
+/// Pick the first move most of the samples agree on, weighted by their evals. Returns the lines of one of its voters with the averaged score
+pub fn vote(samples: Vec<Vec<Line>>) -> Option<Vec<Line>> {
+    let tally = tally(&samples)?;
+    outputln!(
+        "info string votes: {}",
+        tally
+            .iter()
+            .map(|(mov, voters, weight)| format!("{mov} {} ({weight:.2})", voters.len()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+
+    let lines = winner(&samples, &tally)?;
+    outputln!(
+        "info string {} won the vote of {} samples, their average score is {}",
+        lines[0].bestmove,
+        samples.len(),
+        lines[0].score
+    );
+    Some(lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn sample(mov: &str, cp: i32) -> Vec<Line> {
+        let bestmove = ChessMove::from_str(mov).unwrap();
+        vec![Line { bestmove, pv: mov.to_string(), ponder: None, score: Score::Cp(cp), depth: 1, wdl: None }]
+    }
+
+    #[test]
+    fn the_leader_of_the_samples_so_far_gets_the_average_score() {
+        let samples = [sample("e2e4", 40), sample("d2d4", 20), sample("e2e4", 20)];
+        let lines = leader(&samples).unwrap();
+        assert_eq!(lines[0].bestmove.to_string(), "e2e4");
+        assert_eq!(lines[0].score, Score::Cp(30));
+
+        assert!(leader(&[]).is_none());
+    }
+}
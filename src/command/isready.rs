@@ -5,10 +5,9 @@ use crate::state::State;
 pub struct IsReadyCommand;
 
 impl ICommand for IsReadyCommand {
-    async fn execute(&self, _args: Vec<String>, state: &mut State) -> CommandResult {
-        if state.cancel_go.lock().await.is_none() {
-            outputln!("readyok");
-        }
+    async fn execute(&self, _args: Vec<String>, _state: &mut State) -> CommandResult {
+        // answered right away even while searching, go infinite and go ponder only end with stop or ponderhit
+        outputln!("readyok");
         Ok(())
     }
-}
\ No newline at end of file
+}
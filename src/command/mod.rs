@@ -9,7 +9,9 @@ use crate::command::{
     quit::QuitCommand,
     isready::IsReadyCommand,
     license::LicenseCommand,
-    setoption::SetOptionCommand
+    setoption::SetOptionCommand,
+    ponderhit::PonderHitCommand,
+    ucinewgame::UciNewGameCommand
 };
 use crate::utils::consume_args;
 
@@ -22,6 +24,8 @@ mod quit;
 mod isready;
 mod license;
 mod setoption;
+mod ponderhit;
+mod ucinewgame;
 
 pub enum Command {
     Uci(UciCommand),
@@ -33,6 +37,8 @@ pub enum Command {
     IsReady(IsReadyCommand),
     License(LicenseCommand),
     SetOption(SetOptionCommand),
+    PonderHit(PonderHitCommand),
+    UciNewGame(UciNewGameCommand),
 }
 
 pub type CommandResult = Result<(), String>;
@@ -73,6 +79,8 @@ impl<'a> TryFrom<&'a str> for Command {
             "isready" => Ok(Command::IsReady(IsReadyCommand)),
             "license" => Ok(Command::License(LicenseCommand)),
             "setoption" => Ok(Command::SetOption(SetOptionCommand)),
+            "ponderhit" => Ok(Command::PonderHit(PonderHitCommand)),
+            "ucinewgame" => Ok(Command::UciNewGame(UciNewGameCommand)),
             _ => Err(())
         }
     }
@@ -90,6 +98,8 @@ impl ICommand for Command {
             Command::IsReady(command) => command.execute(args, state).await,
             Command::License(command) => command.execute(args, state).await,
             Command::SetOption(command) => command.execute(args, state).await,
+            Command::PonderHit(command) => command.execute(args, state).await,
+            Command::UciNewGame(command) => command.execute(args, state).await,
         }
     }
 }
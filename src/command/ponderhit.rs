@@ -0,0 +1,11 @@
+use crate::command::{CommandResult, ICommand};
+use crate::state::State;
+
+pub struct PonderHitCommand;
+
+impl ICommand for PonderHitCommand {
+    async fn execute(&self, _args: Vec<String>, state: &mut State) -> CommandResult {
+        if let Some(x) = state.ponder_hit.lock().await.clone() { x.cancel(); }
+        Ok(())
+    }
+}
@@ -1,33 +1,35 @@
 use std::str::FromStr;
 
-use chess::{Board, ChessMove, MoveGen};
+use chess::{Board, ChessMove};
 
+use crate::chess960::{self, Castling};
 use crate::command::{CommandResult, ICommand};
 use crate::outputln;
+use crate::state::game::Game;
 use crate::state::State;
 use crate::utils::consume_args;
 
 pub struct PositionCommand;
 
 impl PositionCommand {
-    fn startpos(&self, state: &mut State) {
-        state.board = Board::default();
-    }
-
-    fn fen(&self, args: &Vec<String>, state: &mut State) -> CommandResult {
-        let fen = args
-            .iter()
+    fn fen(&self, args: &[String]) -> String {
+        args.iter()
             .take_while(|x| *x != "moves")
             .map(|x| x.to_string())
             .collect::<Vec<_>>()
-            .join(" ");
+            .join(" ")
+    }
 
-        match Board::from_str(&fen) {
+    /// The board and, in a Chess960 game, its castling rights. GUIs send Shredder-FEN or X-FEN in Chess960 games
+    fn parse(&self, fen: &str, chess960: bool) -> Result<(Board, Option<Castling>), String> {
+        if chess960 {
+            let (board, castling) = chess960::parse_fen(fen)?;
+            return Ok((board, Some(castling)));
+        }
+
+        match Board::from_str(fen) {
             Err(err) => Err(format!("{err}")),
-            Ok(board) => {
-                state.board = board;
-                Ok(())
-            }
+            Ok(board) => Ok((board, None)),
         }
     }
 }
@@ -41,39 +43,55 @@ impl ICommand for PositionCommand {
         let subarg = args.first().unwrap().clone();
         let args = consume_args(args);
 
-        if subarg == "startpos" {
-            self.startpos(state);
-        }
-
-        if subarg == "fen" {
-            self.fen(&args, state)?;
-        }
+        let chess960 = state.options.uci_chess960;
+        let (start, castling, start_fen) = match subarg.as_str() {
+            "startpos" => {
+                let (board, castling) = self.parse(&Board::default().to_string(), chess960)?;
+                (board, castling, None)
+            }
+            "fen" => {
+                let fen = self.fen(&args);
+                let (board, castling) = self.parse(&fen, chess960)?;
+                (board, castling, Some(fen))
+            }
+            _ => return Ok(()),
+        };
 
-        if subarg != "startpos" && subarg != "fen" {
-            return Ok(());
-        }
+        let moves = args
+            .iter()
+            .skip_while(|x| *x != "moves")
+            .skip(1)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        // the gui usually sends the whole game again with one or two new moves, no need to replay all of it
+        let mut game = if state.game.is_continued_by(start_fen.as_deref(), &moves, chess960) {
+            state.game.clone()
+        } else {
+            Game::new(start, start_fen, castling)
+        };
 
-        let mut moves_start = false;
-        for arg in args {
-            if arg == "moves" {
-                moves_start = true;
-                continue;
-            }
-            if moves_start {
-                match ChessMove::from_str(&arg) {
-                    Ok(mov) => {
-                        if state.board.piece_on(mov.get_source()).is_none() {
-                            return Err(format!("Invalid move {arg}: There's no piece on {}!", mov.get_source()));
-                        }
-                        state.board = state.board.make_move_new(mov)
-                    },
-                    Err(err) => {
-                        return Err(format!("Invalid move {arg}: {err}"));
+        // the previous position is kept if any of the moves is wrong
+        for (ply, arg) in moves.iter().enumerate().skip(game.moves().len()) {
+            match ChessMove::from_str(arg) {
+                Ok(mov) => {
+                    if !game.is_legal(mov) {
+                        return Err(format!(
+                            "Illegal move {arg} at ply {} in position {}, keeping the previous position",
+                            ply + 1,
+                            game.fen()
+                        ));
                     }
+                    game.push(mov);
+                },
+                Err(err) => {
+                    return Err(format!("Invalid move {arg} at ply {}: {err}, keeping the previous position", ply + 1));
                 }
             }
         }
 
+        state.game = game;
+
         #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
         #[serde(rename_all = "camelCase")]
         struct Prompt {
@@ -82,8 +100,8 @@ impl ICommand for PositionCommand {
         }
 
         let p = Prompt {
-            fen: state.board.to_string(),
-            legal_moves: MoveGen::new_legal(&state.board).map(|x| x.to_string()).collect::<Vec<String>>()
+            fen: state.game.fen(),
+            legal_moves: state.game.legal_moves().iter().map(|x| x.to_string()).collect::<Vec<String>>()
         };
 
         if state.options.debug {
@@ -92,4 +110,69 @@ impl ICommand for PositionCommand {
 
         Ok(())
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use crate::command::Command;
+    use crate::state::State;
+    use crate::utils::tests::take_output;
+
+    async fn position(state: &mut State, args: &str) -> Vec<String> {
+        take_output();
+        Command::process_line(format!("position {args}"), state).await;
+        take_output()
+    }
+
+    #[tokio::test]
+    async fn illegal_moves_keep_the_previous_position() {
+        let mut state = State::default();
+        position(&mut state, "startpos moves e2e4 e7e5").await;
+        let fen = state.game.fen();
+
+        // white moving twice in a row
+        let output = position(&mut state, "startpos moves e2e4 e7e5 g1f3 b1c3").await;
+        assert!(output[0].starts_with("info string error: Illegal move b1c3 at ply 4"), "{output:?}");
+        assert_eq!(state.game.fen(), fen);
+
+        let output = position(&mut state, "startpos moves e2e4 e7e5 e4e6").await;
+        assert!(output[0].starts_with("info string error: Illegal move e4e6 at ply 3"), "{output:?}");
+        assert_eq!(state.game.fen(), fen);
+
+        let output = position(&mut state, "startpos moves e2e4 nonsense").await;
+        assert!(output[0].starts_with("info string error: Invalid move nonsense at ply 2"), "{output:?}");
+        assert_eq!(state.game.fen(), fen);
+    }
+
+    #[tokio::test]
+    async fn a_continued_game_ends_up_where_a_new_one_would() {
+        let moves = "startpos moves e2e4 e7e5 g1f3 b8c6 f1b5 a7a6";
+        let mut fresh = State::default();
+        position(&mut fresh, moves).await;
+
+        let mut continued = State::default();
+        position(&mut continued, "startpos moves e2e4 e7e5").await;
+        position(&mut continued, "startpos moves e2e4 e7e5 g1f3 b8c6").await;
+        position(&mut continued, moves).await;
+        assert_eq!(continued.game.fen(), fresh.game.fen());
+        assert_eq!(continued.game.history(), fresh.game.history());
+    }
+
+    #[tokio::test]
+    async fn a_different_game_is_not_continued() {
+        let mut state = State::default();
+        position(&mut state, "startpos moves e2e4 e7e5").await;
+
+        position(&mut state, "startpos moves d2d4").await;
+        assert_eq!(state.game.history(), "1. d2d4");
+
+        let fen = "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1";
+        position(&mut state, &format!("fen {fen} moves e1d1")).await;
+        position(&mut state, &format!("fen {fen} moves e1d1 e8d8")).await;
+        assert_eq!(state.game.start_fen(), Some(fen));
+        assert_eq!(state.game.history(), "1. e1d1 e8d8");
+
+        position(&mut state, "startpos").await;
+        assert_eq!(state.game.start_fen(), None);
+        assert!(state.game.moves().is_empty());
+    }
+}
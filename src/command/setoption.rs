@@ -9,7 +9,7 @@ pub struct SetOptionCommand;
 impl ICommand for SetOptionCommand {
     async fn execute(&self, args: Vec<String>, state: &mut State) -> CommandResult {
         let name = args.iter().position(|x| x == "name").and_then(|x| args.get(x + 1).cloned());
-        let value = args.iter().position(|x| x == "value").and_then(|x| Some(args.iter().cloned().skip(x + 1).collect::<Vec<String>>().join(" ")));
+        let value = args.iter().position(|x| x == "value").map(|x| args.iter().skip(x + 1).cloned().collect::<Vec<String>>().join(" "));
 
         if name.is_none() {
             outputln!("info string error: setoption name is required");
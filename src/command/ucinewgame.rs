@@ -0,0 +1,11 @@
+use crate::command::{CommandResult, ICommand};
+use crate::state::State;
+
+pub struct UciNewGameCommand;
+
+impl ICommand for UciNewGameCommand {
+    async fn execute(&self, _args: Vec<String>, state: &mut State) -> CommandResult {
+        state.new_game().await;
+        Ok(())
+    }
+}
@@ -1,5 +1,7 @@
 use std::fmt::Write;
 
+use crate::chess960;
+
 #[derive(Debug, Clone, Copy)]
 pub enum ChessPieces {
     BlackRook,
@@ -59,15 +61,15 @@ impl ChessPieces {
     }
 
     pub fn is_white(&self) -> bool {
-        match self {
-            Self::WhiteRook => true,
-            Self::WhiteKnight => true,
-            Self::WhiteBishop => true,
-            Self::WhiteQueen => true,
-            Self::WhiteKing => true,
-            Self::WhitePawn => true,
-            _ => false,
-        }
+        matches!(
+            self,
+            Self::WhiteRook
+                | Self::WhiteKnight
+                | Self::WhiteBishop
+                | Self::WhiteQueen
+                | Self::WhiteKing
+                | Self::WhitePawn
+        )
     }
 
     pub fn value(&self) -> u8 {
@@ -148,7 +150,7 @@ pub fn fen2md(fen: String) -> Result<String, String> {
             }
             file += 1;
             
-            let char_file = (('a' as u8) + (file - 1)) as char;
+            let char_file = (b'a' + (file - 1)) as char;
             match ChessPieces::try_from(chr) {
                 Ok(v) => {
                     if v.is_white() {
@@ -167,10 +169,10 @@ pub fn fen2md(fen: String) -> Result<String, String> {
     }
 
     writeln!(markdown, "\n## Material\n").unwrap();
-    write!(markdown, "White's material count: {white_material}\n").unwrap();
-    write!(markdown, "Black's material count: {black_material}\n").unwrap();
+    writeln!(markdown, "White's material count: {white_material}").unwrap();
+    writeln!(markdown, "Black's material count: {black_material}").unwrap();
     if white_material == black_material {
-        write!(markdown, "The players are equal on material.\n").unwrap();
+        writeln!(markdown, "The players are equal on material.").unwrap();
     } else {
         if white_material > black_material {
             write!(markdown, "White has a +{} material advantage", white_material - black_material).unwrap();
@@ -182,7 +184,7 @@ pub fn fen2md(fen: String) -> Result<String, String> {
 
     write!(markdown, "\n### Other\n").unwrap();
 
-    if invalid_pieces.len() != 0 {
+    if !invalid_pieces.is_empty() {
         return Err(format!("Invalid pieces: {}", invalid_pieces.join(", ")));
     }
 
@@ -198,12 +200,25 @@ pub fn fen2md(fen: String) -> Result<String, String> {
         writeln!(markdown, "No castling available").unwrap();
     } else {
         writeln!(markdown, "Castling options are:").unwrap();
+        let placement = fen.split_whitespace().next().unwrap_or_default();
         for castle in castles.chars() {
             let castle = match castle {
-                'K' => "White can castle short",
-                'Q' => "White can castle long",
-                'k' => "Black can castle short",
-                'q' => "Black can castle long",
+                'K' => "White can castle short".to_string(),
+                'Q' => "White can castle long".to_string(),
+                'k' => "Black can castle short".to_string(),
+                'q' => "Black can castle long".to_string(),
+                // Shredder-FEN and X-FEN name the file of the castling rook in Chess960
+                'A'..='H' | 'a'..='h' => {
+                    let Some((king, rook)) = chess960::castling_files(placement, castle) else {
+                        return Err(format!("Invalid castling option: {castle}, there is no rook to castle with"));
+                    };
+                    format!(
+                        "{} can castle {} with the rook on the {}-file (Chess960)",
+                        if castle.is_ascii_uppercase() { "White" } else { "Black" },
+                        if rook > king { "short" } else { "long" },
+                        (b'a' + rook as u8) as char
+                    )
+                }
                 _ => return Err(format!("Invalid castling option: {castle}"))
             };
             writeln!(markdown, "- {castle}").unwrap();
@@ -222,3 +237,15 @@ pub fn fen2md(fen: String) -> Result<String, String> {
 
     Ok(markdown)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chess960_castling_rights_are_explained() {
+        let markdown = fen2md("bqnbrk1r/pppppppp/8/8/8/8/PPPPPPPP/BQNBRK1R w HEhe - 0 1".into()).unwrap();
+        assert!(markdown.contains("White can castle short with the rook on the h-file (Chess960)"));
+        assert!(markdown.contains("Black can castle long with the rook on the e-file (Chess960)"));
+    }
+}
@@ -3,6 +3,8 @@ use tokio::io::{AsyncBufReadExt, BufReader};
 use crate::command::Command;
 use crate::state::State;
 
+mod backend;
+mod chess960;
 mod command;
 mod state;
 mod utils;
@@ -3,13 +3,15 @@ use std::process::exit;
 use std::sync::Arc;
 use std::time::Duration;
 
-use chess::Board;
 use tokio::sync::Notify;
 use tokio::sync::Mutex;
 use tokio_util::sync::CancellationToken;
 
+use crate::backend::Cache;
+use crate::state::game::Game;
 use crate::state::options::Options;
 
+pub mod game;
 pub mod options;
 
 pub type CancelToken = Arc<Mutex<Option<CancellationToken>>>;
@@ -17,16 +19,33 @@ pub type GoStoppedNotification = Arc<Mutex<Notify>>;
 
 #[derive(Clone)]
 pub struct State {
-    pub board: Board,
+    pub game: Game,
     pub cancel_go: CancelToken,
+    pub ponder_hit: CancelToken,
     pub go_stopped_notification: GoStoppedNotification,
+    pub cache: Cache,
     pub options: Options
 }
 
 impl State {
     /// Try to quit gracefully, force exit(0) within 500 ms
     pub async fn quit(&self) {
-        if let Some(token) = self.cancel_go.lock().await.take() {
+        self.stop_go().await;
+        exit(0);
+    }
+
+    /// Forget everything about the previous game. The options are kept
+    pub async fn new_game(&mut self) {
+        self.stop_go().await;
+        self.game = Game::default();
+        self.cache.lock().unwrap().clear();
+    }
+
+    /// Cancel the running go command and give it up to 500 ms to stop
+    async fn stop_go(&self) {
+        // the go task clears the token itself once it is done
+        let token = self.cancel_go.lock().await.clone();
+        if let Some(token) = token {
             token.cancel();
             
             // allow for up to 500 milliseconds for the go command to stop gracefully
@@ -39,14 +58,16 @@ impl State {
                 tokio::time::sleep(Duration::from_millis(5)).await;
             }
         }
-
-        exit(0);
     }
 }
 
 impl Debug for State {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_fmt(format_args!("Board FEN: {}\n", self.board))?;
+        f.write_fmt(format_args!("Board FEN: {}\n", self.game.fen()))?;
+        if let Some(fen) = self.game.start_fen() {
+            f.write_fmt(format_args!("Start FEN: {fen}\n"))?;
+        }
+        f.write_fmt(format_args!("Moves: {}\n", self.game.history()))?;
         f.write_fmt(format_args!("The go command is {}",
             match self.cancel_go.try_lock() {
                 Ok(cancel) => if cancel.is_some() { "running" } else { "not running" },
@@ -60,9 +81,11 @@ impl Debug for State {
 impl Default for State {
     fn default() -> Self {
         Self {
-            board: Board::default(),
+            game: Game::default(),
             cancel_go: Arc::new(Mutex::new(None)),
+            ponder_hit: Arc::new(Mutex::new(None)),
             go_stopped_notification: Arc::new(Mutex::new(Notify::new())),
+            cache: Cache::default(),
             options: Options::default()
         }
     }
@@ -0,0 +1,276 @@
+use std::fmt::{Display, Write};
+
+use chess::{Board, BoardStatus, ChessMove, Color, MoveGen, Piece};
+
+use crate::chess960::{self, Castling};
+
+/// Halfmove clock value at which the fifty-move rule ends the game
+const FIFTY_MOVES: u32 = 100;
+
+/// The game as the GUI described it with the last `position` command
+#[derive(Debug, Clone)]
+pub struct Game {
+    /// The fen the game started from, `None` for the standard starting position
+    start_fen: Option<String>,
+    moves: Vec<ChessMove>,
+    /// Every position of the game, starting with the initial one. Never empty
+    positions: Vec<Board>,
+    /// The halfmove clock of every position. `chess::Board` doesn't keep track of it, so we have to
+    clocks: Vec<u32>,
+    /// The castling rights of every position in a Chess960 game, `None` in a standard game where the boards keep them
+    castling: Vec<Option<Castling>>,
+}
+
+/// A rule by which a move ends the game in a draw
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrawRule {
+    Repetition,
+    FiftyMoves,
+}
+
+impl Display for DrawRule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Repetition => write!(f, "threefold repetition"),
+            Self::FiftyMoves => write!(f, "fifty-move rule"),
+        }
+    }
+}
+
+impl Default for Game {
+    fn default() -> Self {
+        Self::new(Board::default(), None, None)
+    }
+}
+
+impl Game {
+    /// Start a game. Chess960 games pass their castling rights, their boards must not have any of their own
+    pub fn new(start: Board, start_fen: Option<String>, castling: Option<Castling>) -> Self {
+        let clock = Self::fen_field(start_fen.as_deref(), 4).unwrap_or(0);
+        Self { start_fen, moves: vec![], positions: vec![start], clocks: vec![clock], castling: vec![castling] }
+    }
+
+    fn fen_field(fen: Option<&str>, index: usize) -> Option<u32> {
+        fen?.split_whitespace().nth(index)?.parse().ok()
+    }
+
+    /// The current position
+    pub fn board(&self) -> Board {
+        *self.positions.last().unwrap()
+    }
+
+    pub fn start_fen(&self) -> Option<&str> {
+        self.start_fen.as_deref()
+    }
+
+    pub fn moves(&self) -> &[ChessMove] {
+        &self.moves
+    }
+
+    pub fn is_chess960(&self) -> bool {
+        self.castling[0].is_some()
+    }
+
+    /// The Chess960 castling rights of the current position
+    fn castling(&self) -> Option<Castling> {
+        *self.castling.last().unwrap()
+    }
+
+    /// The legal moves of the current position, Chess960 castling included
+    pub fn legal_moves(&self) -> Vec<ChessMove> {
+        let board = self.board();
+        let mut moves = MoveGen::new_legal(&board).collect::<Vec<_>>();
+        if let Some(castling) = self.castling() {
+            moves.extend(castling.moves(&board));
+        }
+        moves
+    }
+
+    pub fn is_legal(&self, mov: ChessMove) -> bool {
+        let board = self.board();
+        board.legal(mov) || self.castling().is_some_and(|x| x.moves(&board).contains(&mov))
+    }
+
+    /// The position after `mov`, which has to be legal
+    pub fn board_after(&self, mov: ChessMove) -> Board {
+        let board = self.board();
+        match self.castling() {
+            Some(_) if chess960::is_castle(&board, mov) => chess960::castle(&board, mov),
+            _ => board.make_move_new(mov),
+        }
+    }
+
+    /// The current halfmove clock, counting the halfmoves since the last capture or pawn move
+    pub fn halfmove_clock(&self) -> u32 {
+        *self.clocks.last().unwrap()
+    }
+
+    fn start_fullmove(&self) -> u32 {
+        Self::fen_field(self.start_fen.as_deref(), 5).unwrap_or(1)
+    }
+
+    /// The fen of the current position, with the move clocks `Board`'s fen gets wrong
+    pub fn fen(&self) -> String {
+        let black_moves = match self.positions[0].side_to_move() {
+            Color::White => self.moves.len() / 2,
+            Color::Black => self.moves.len().div_ceil(2),
+        };
+        let fullmove = self.start_fullmove() + black_moves as u32;
+
+        let board = self.board().to_string();
+        let mut position = board.split_whitespace().take(4).collect::<Vec<_>>();
+        let castling = self.castling().map(|x| x.fen_field());
+        if let Some(castling) = &castling {
+            position[2] = castling;
+        }
+        format!("{} {} {fullmove}", position.join(" "), self.halfmove_clock())
+    }
+
+    fn clock_after(&self, mov: ChessMove) -> u32 {
+        let board = self.board();
+        let capture = board.color_on(mov.get_dest()) == Some(!board.side_to_move());
+        if board.piece_on(mov.get_source()) == Some(Piece::Pawn) || capture {
+            0
+        } else {
+            self.halfmove_clock() + 1
+        }
+    }
+
+    pub fn push(&mut self, mov: ChessMove) {
+        let clock = self.clock_after(mov);
+        let castling = self.castling().map(|x| x.after(&self.board(), mov));
+        let board = self.board_after(mov);
+        self.moves.push(mov);
+        self.positions.push(board);
+        self.clocks.push(clock);
+        self.castling.push(castling);
+    }
+
+    /// How many times the position after `mov` has already occurred in this game
+    pub fn occurrences_after(&self, mov: ChessMove) -> usize {
+        let clock = self.clock_after(mov) as usize;
+        let position = (self.board_after(mov), self.castling().map(|x| x.after(&self.board(), mov)));
+
+        // positions before the last capture or pawn move can't come back
+        let positions = self.positions.iter().copied().zip(self.castling.iter().copied());
+        positions.rev().take(clock).filter(|x| *x == position).count()
+    }
+
+    /// Whether `mov` ends the game in a draw by repetition or by the fifty-move rule
+    pub fn draw_after(&self, mov: ChessMove) -> Option<DrawRule> {
+        if self.board_after(mov).status() == BoardStatus::Checkmate {
+            return None;
+        }
+        if self.occurrences_after(mov) >= 2 {
+            return Some(DrawRule::Repetition);
+        }
+        if self.clock_after(mov) >= FIFTY_MOVES {
+            return Some(DrawRule::FiftyMoves);
+        }
+        None
+    }
+
+    /// Whether a game of the same variant from `start_fen` with `moves` continues this one, so that only the new moves
+    /// have to be played
+    pub fn is_continued_by(&self, start_fen: Option<&str>, moves: &[String], chess960: bool) -> bool {
+        self.is_chess960() == chess960
+            && self.start_fen() == start_fen
+            && self.moves.len() <= moves.len()
+            && self.moves.iter().zip(moves).all(|(mov, uci)| mov.to_string() == *uci)
+    }
+
+    /// The moves played so far, numbered like in a PGN but in UCI notation
+    pub fn history(&self) -> String {
+        let mut number = self.start_fullmove();
+
+        let mut history = String::new();
+        let mut side = self.positions[0].side_to_move();
+        for (i, mov) in self.moves.iter().enumerate() {
+            match side {
+                Color::White => write!(history, "{number}. {mov} ").unwrap(),
+                Color::Black if i == 0 => write!(history, "{number}... {mov} ").unwrap(),
+                Color::Black => write!(history, "{mov} ").unwrap(),
+            }
+            if side == Color::Black {
+                number += 1;
+            }
+            side = !side;
+        }
+
+        history.trim_end().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+
+    fn mov(uci: &str) -> ChessMove {
+        ChessMove::from_str(uci).unwrap()
+    }
+
+    fn play(fen: Option<&str>, moves: &[&str]) -> Game {
+        let board = fen.map(|x| Board::from_str(x).unwrap()).unwrap_or_default();
+        let mut game = Game::new(board, fen.map(|x| x.to_string()), None);
+        for uci in moves {
+            game.push(mov(uci));
+        }
+        game
+    }
+
+    #[test]
+    fn repetitions_are_counted_since_the_last_capture_or_pawn_move() {
+        let game = play(None, &["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1"]);
+        assert_eq!(game.occurrences_after(mov("f6g8")), 2);
+        assert_eq!(game.occurrences_after(mov("b8c6")), 0);
+
+        let game = play(None, &["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "e7e6", "g1f3", "f8e7", "f3g1", "e7f8", "g1f3"]);
+        assert_eq!(game.occurrences_after(mov("f6g8")), 0);
+    }
+
+    #[test]
+    fn the_third_occurrence_draws() {
+        let game = play(None, &["g1f3", "g8f6", "f3g1", "f6g8", "g1f3"]);
+        assert_eq!(game.draw_after(mov("g8f6")), None);
+
+        let game = play(None, &["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1"]);
+        assert_eq!(game.draw_after(mov("f6g8")), Some(DrawRule::Repetition));
+        assert_eq!(game.draw_after(mov("b8c6")), None);
+    }
+
+    #[test]
+    fn the_hundredth_quiet_halfmove_draws() {
+        let game = play(Some("8/8/4k3/8/8/3K4/8/7R w - - 99 80"), &[]);
+        assert_eq!(game.draw_after(mov("h1h2")), Some(DrawRule::FiftyMoves));
+
+        let game = play(Some("8/8/4k3/8/8/3K4/8/7R w - - 98 80"), &[]);
+        assert_eq!(game.draw_after(mov("h1h2")), None);
+    }
+
+    #[test]
+    fn mate_beats_the_fifty_move_rule() {
+        let game = play(Some("6k1/5ppp/8/8/8/8/8/R5K1 w - - 99 80"), &[]);
+        assert_eq!(game.draw_after(mov("a1a8")), None);
+    }
+
+    #[test]
+    fn the_fen_keeps_the_move_clocks() {
+        let game = play(Some("4k3/8/8/8/8/8/4P3/4K2R w K - 12 30"), &["h1h2", "e8d8", "e2e4"]);
+        assert_eq!(game.fen(), "3k4/8/8/8/4P3/8/7R/4K3 b - - 0 31");
+    }
+
+    #[test]
+    fn chess960_castling_is_played_and_written_as_king_takes_rook() {
+        let (board, castling) = chess960::parse_fen("bqnbrk1r/pppppppp/8/8/8/8/PPPPPPPP/BQNBRK1R w HEhe - 0 1").unwrap();
+        let mut game = Game::new(board, None, Some(castling));
+        assert!(game.legal_moves().contains(&mov("f1h1")));
+        assert!(game.is_legal(mov("f1h1")));
+
+        game.push(mov("f1h1"));
+        assert_eq!(game.fen(), "bqnbrk1r/pppppppp/8/8/8/8/PPPPPPPP/BQNBRRK1 b he - 1 1");
+        assert!(game.is_continued_by(None, &["f1h1".to_string(), "f8h8".to_string()], true));
+        assert!(!game.is_continued_by(None, &["f1h1".to_string()], false));
+    }
+}
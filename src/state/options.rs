@@ -1,12 +1,53 @@
 pub trait UciOption {
     fn uci_type() -> &'static str;
+    fn uci_vars() -> Vec<&'static str> { vec![] }
 }
 
 impl UciOption for u8 { fn uci_type() -> &'static str { "spin" } }
 impl UciOption for u16 { fn uci_type() -> &'static str { "spin" } }
+impl UciOption for u32 { fn uci_type() -> &'static str { "spin" } }
 impl UciOption for bool { fn uci_type() -> &'static str { "check" } }
 impl UciOption for String { fn uci_type() -> &'static str { "string" } }
 
+macro_rules! combo {
+    ( $name:ident { $( $variant:ident ),* $(,)? } ) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+        pub enum $name {
+            $( $variant, )*
+        }
+
+        impl UciOption for $name {
+            fn uci_type() -> &'static str { "combo" }
+            fn uci_vars() -> Vec<&'static str> { vec![$( stringify!($variant), )*] }
+        }
+
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                match self {
+                    $( Self::$variant => f.write_str(stringify!($variant)), )*
+                }
+            }
+        }
+
+        impl std::str::FromStr for $name {
+            type Err = String;
+            fn from_str(value: &str) -> Result<Self, Self::Err> {
+                $(
+                    if value.eq_ignore_ascii_case(stringify!($variant)) {
+                        return Ok(Self::$variant);
+                    }
+                )*
+                Err(format!("{value} is not one of: {}", <Self as UciOption>::uci_vars().join(", ")))
+            }
+        }
+    };
+}
+
+combo!(SearchMode { Simple, Tools });
+combo!(BackendKind { Responses, ChatCompletions, Mock, Replay });
+combo!(DrawMoves { Mark, Override });
+combo!(EnsemblePolicy { Vote, Strongest, Escalate });
+
 macro_rules! options {
     (@format_option $name:literal, $type:ty, $value:expr, $min:expr, $max:expr) => {
         format!("option name {} type {} default {} min {} max {}",
@@ -19,10 +60,11 @@ macro_rules! options {
     };
 
     (@format_option $name:literal, $type:ty, $value:expr,) => {
-        format!("option name {} type {} default {}",
+        format!("option name {} type {} default {}{}",
             $name,
             <$type as UciOption>::uci_type(),
-            $value
+            $value,
+            <$type as UciOption>::uci_vars().iter().map(|x| format!(" var {x}")).collect::<String>()
         )
     };
 
@@ -68,10 +110,30 @@ options! {
     threads: u16 = 1 => "Threads" [1, 256],
     debug: bool = false => "Debug",
     output_reasoning: bool = false => "OutputReasoning",
+    backend: BackendKind = BackendKind::Responses => "Backend",
     apimodel: String = String::from("openai/gpt-oss-20b") => "APIModel",
     apibaseurl: String = String::from("<unset>") => "APIBaseURL",
     apikey: String = String::from("<unset>") => "APIKey",
     apimaxtries: u8 = 3 => "APIMaxTries",
+    api_timeout_ms: u32 = 120000 => "APITimeoutMs" [0, 3600000],
+    ensemble_file: String = String::new() => "EnsembleFile",
+    ensemble_policy: EnsemblePolicy = EnsemblePolicy::Vote => "EnsemblePolicy",
+    chat_json_schema: bool = true => "ChatJsonSchema",
+    search_mode: SearchMode = SearchMode::Simple => "SearchMode",
+    apimaxturns: u8 = 16 => "APIMaxTurns" [1, 255],
+    multipv: u8 = 1 => "MultiPV" [1, 16],
+    ponder: bool = true => "Ponder",
+    move_overhead: u16 = 100 => "MoveOverhead" [0, 10000],
+    draw_moves: DrawMoves = DrawMoves::Mark => "DrawMoves",
+    uci_chess960: bool = false => "UCI_Chess960",
+    uci_limit_strength: bool = false => "UCI_LimitStrength",
+    uci_elo: u16 = 1500 => "UCI_Elo" [800, 2800],
+    uci_show_wdl: bool = false => "UCI_ShowWDL",
+    mock_response: String = String::new() => "MockResponse",
+    mock_script_file: String = String::new() => "MockScriptFile",
+    mock_latency_ms: u16 = 0 => "MockLatencyMs" [0, 60000],
+    record_file: String = String::new() => "RecordFile",
+    replay_file: String = String::new() => "ReplayFile",
     fenasmd: bool = false => "FenAsMarkdown",
     additional_instructions: String = String::new() => "AdditionalInstructions",
     additional_instructions_file: String = String::new() => "AdditionalInstructionsFile",
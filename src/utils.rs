@@ -13,13 +13,38 @@ macro_rules! output {
 
 #[macro_export]
 macro_rules! outputln {
-    ($($arg:tt)*) => {        
-        println!($($arg)*);
-        $crate::utils::flush();
+    () => {
+        $crate::utils::output_line(String::new());
+    };
+    ($($arg:tt)*) => {
+        $crate::utils::output_line(format!($($arg)*));
     };
 }
 
+pub fn output_line(line: String) {
+    #[cfg(test)]
+    tests::OUTPUT.with_borrow_mut(|x| x.push(line.clone()));
+
+    println!("{line}");
+    flush();
+}
+
 pub fn consume_args(mut args: Vec<String>) -> Vec<String> {
     args.remove(0);
     args
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::cell::RefCell;
+
+    thread_local! {
+        /// Every line printed on this thread, so tests on a single-threaded runtime can read what the engine said
+        pub static OUTPUT: RefCell<Vec<String>> = const { RefCell::new(vec![]) };
+    }
+
+    /// The lines printed on this thread since the last call
+    pub fn take_output() -> Vec<String> {
+        OUTPUT.take()
+    }
+}